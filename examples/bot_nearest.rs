@@ -0,0 +1,86 @@
+//! An example bot for `--bot`, which aims at the nearest enemy cannon and fires whenever the
+//! line of fire crosses more enemy blocks than its own.
+//!
+//! ```text
+//! cargo build --example bot_nearest
+//! cargo run -- --bot 2=target/debug/examples/bot_nearest
+//! ```
+
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value};
+
+fn main() {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for line in io::stdin().lock().lines().map_while(Result::ok) {
+        let observation: Value = match serde_json::from_str(&line) {
+            Ok(observation) => observation,
+            Err(_) => continue,
+        };
+        let reply = decide(&observation).unwrap_or_else(|| json!({ "angle": 0.0, "fire": true }));
+        if writeln!(out, "{}", reply)
+            .and_then(|_| out.flush())
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+fn decide(observation: &Value) -> Option<Value> {
+    let id = observation["id"].as_i64()?;
+    let position = point(&observation["position"])?;
+    let (min, max) = (
+        observation["min_angle"].as_f64()?,
+        observation["max_angle"].as_f64()?,
+    );
+
+    let distance = |p: [f64; 2]| (p[0] - position[0]).hypot(p[1] - position[1]);
+    let nearest = observation["enemies"]
+        .as_array()?
+        .iter()
+        .filter_map(|enemy| point(&enemy["position"]))
+        .min_by(|a, b| distance(*a).total_cmp(&distance(*b)))?;
+    let mut angle = (nearest[1] - position[1])
+        .atan2(nearest[0] - position[0])
+        .to_degrees();
+    // angles are in the same range as the limits of the cannon
+    let center = (min + max) / 2.0;
+    angle += ((center - angle) / 360.0).round() * 360.0;
+
+    let fire = enemy_share(observation, id, position, observation["angle"].as_f64()?)? > 0.5;
+    Some(json!({ "angle": angle, "fire": fire, "tick": observation["tick"] }))
+}
+
+/// Share of the blocks along the barrel that belong to someone else.
+fn enemy_share(observation: &Value, id: i64, position: [f64; 2], angle: f64) -> Option<f64> {
+    let [left, top] = point(&observation["field"])?;
+    let width = observation["field"][2].as_f64()?;
+    let grid = observation["grid"]["cells"].as_array()?;
+    let block = width / grid.len() as f64;
+    let (dx, dy) = (angle.to_radians().cos(), angle.to_radians().sin());
+    let (mut enemy, mut total) = (0, 0);
+    let mut step = 1.0;
+    loop {
+        let x = ((position[0] + dx * block * step - left) / block).floor();
+        let y = ((position[1] + dy * block * step - top) / block).floor();
+        if x < 0.0 || y < 0.0 || x >= grid.len() as f64 || y >= grid.len() as f64 {
+            break;
+        }
+        if grid[y as usize][x as usize].as_i64()? != id {
+            enemy += 1;
+        }
+        total += 1;
+        step += 1.0;
+    }
+    Some(if total == 0 {
+        0.0
+    } else {
+        enemy as f64 / total as f64
+    })
+}
+
+fn point(value: &Value) -> Option<[f64; 2]> {
+    Some([value[0].as_f64()?, value[1].as_f64()?])
+}
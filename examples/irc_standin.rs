@@ -0,0 +1,81 @@
+//! A tiny stand-in for an IRC server, enough to try the chat commands of the game locally.
+//!
+//! It accepts any nick, relays channel messages to every other client, and sends each line
+//! typed on its standard input as `<nick> <message>` to everyone, e.g. `ann !boost blue`.
+//!
+//! ```text
+//! cargo run --example irc_standin -- 6667
+//! cargo run -- --irc localhost:6667/colorbattle
+//! ```
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Clients = Arc<Mutex<Vec<(usize, TcpStream)>>>;
+
+fn main() {
+    let port = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "6667".to_string());
+    let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).expect("Unable to listen");
+    println!("IRC stand-in listening on port {}", port);
+    let clients: Clients = Arc::new(Mutex::new(Vec::new()));
+
+    let console = clients.clone();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if let Some((nick, text)) = line.split_once(' ') {
+                let message = format!(
+                    ":{}!{}@localhost PRIVMSG #colorbattle :{}\r\n",
+                    nick, nick, text
+                );
+                broadcast(&console, None, &message);
+            }
+        }
+    });
+
+    for (id, stream) in listener.incoming().flatten().enumerate() {
+        let writer = stream.try_clone().expect("Unable to clone connection");
+        clients.lock().unwrap().push((id, writer));
+        let clients = clients.clone();
+        thread::spawn(move || serve(id, stream, clients));
+    }
+}
+
+fn serve(id: usize, stream: TcpStream, clients: Clients) {
+    let mut writer = stream.try_clone().expect("Unable to clone connection");
+    let mut nick = format!("guest{}", id);
+    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+        let (command, rest) = line.split_once(' ').unwrap_or((&line, ""));
+        let reply = match command {
+            "NICK" => {
+                nick = rest.trim().to_string();
+                format!(":standin 001 {} :Welcome to the stand-in\r\n", nick)
+            }
+            "JOIN" => format!(":{}!{}@localhost JOIN {}\r\n", nick, nick, rest.trim()),
+            "PING" => format!(":standin PONG {}\r\n", rest.trim()),
+            "PRIVMSG" => {
+                let message = format!(":{}!{}@localhost PRIVMSG {}\r\n", nick, nick, rest);
+                println!("{}", message.trim_end());
+                broadcast(&clients, Some(id), &message);
+                continue;
+            }
+            _ => continue,
+        };
+        if writer.write_all(reply.as_bytes()).is_err() {
+            break;
+        }
+    }
+    clients.lock().unwrap().retain(|(other, _)| *other != id);
+}
+
+/// Sends a line to every client except `from`.
+fn broadcast(clients: &Clients, from: Option<usize>, message: &str) {
+    for (id, stream) in clients.lock().unwrap().iter_mut() {
+        if Some(*id) != from {
+            let _ = stream.write_all(message.as_bytes());
+        }
+    }
+}
@@ -1,259 +1,622 @@
+use graphics::{Text, Transformed};
 use opengl_graphics::{GlGraphics, GlyphCache, TextureSettings};
-use piston::{Button, Key, RenderArgs, UpdateArgs};
+use piston::{Button, RenderArgs, UpdateArgs};
+use std::time::Instant;
 
+pub mod arena;
+pub mod balance;
 mod bullet;
 mod cannon;
+pub mod chat;
 mod colors;
 mod common;
+pub mod control;
+mod debug_overlay;
+pub mod environment;
+mod field_texture;
 mod grid;
+pub mod highlights;
+pub mod input_map;
+pub mod ladder;
+mod layout;
+pub mod lockstep;
 mod plinko;
+pub mod replay;
+mod rewind;
+mod screenshot;
+mod simulation;
+pub mod spectator;
+mod timeline;
+pub mod tui;
+pub mod tuning;
 
-use bullet::Bullet;
-use cannon::Cannon;
-use grid::Grid;
-use plinko::{Plinko, PlinkoEvent};
+pub use cannon::CannonParams;
+use cannon::ManualControl;
+use chat::ChatClient;
+use control::ControlServer;
+use debug_overlay::DebugOverlay;
+use field_texture::FieldTexture;
+use highlights::Highlights;
+use input_map::{Action, InputMap};
+use layout::View;
+use lockstep::Lockstep;
+use replay::{Recorder, Replay};
+use rewind::Rewind;
+pub use simulation::Simulation;
+use spectator::SpectatorServer;
 
 pub struct App {
     gl: GlGraphics,
-    grid: Grid,
-    cannons: [Cannon; 4],
-    bullets: Vec<Bullet>,
-    field_rect: [f64; 4],
-    plinkos: [Plinko; 4],
+    sim: Simulation,
+    field: FieldTexture,
     glyphs: GlyphCache<'static>,
+    debug: DebugOverlay,
+    paused: bool,
+    step_requested: bool,
+    speed_index: usize,
+    pending_ticks: f64,
+    view: View,
+    cursor: [f64; 2],
+    mouse_seat: Option<usize>,
+    input: InputMap,
+    screenshot_requested: bool,
+    snapshot_path: String,
+    grid_path: String,
+    rewind: Rewind,
+    // the timeline bar is being dragged
+    is_seeking: bool,
+    lockstep: Option<Lockstep>,
+    recorder: Option<Recorder>,
+    // a recorded match is being watched rather than played
+    replay: Option<Replay>,
+    highlights: Option<Highlights>,
+    spectators: Option<SpectatorServer>,
+    chat: Option<ChatClient>,
+    control: Option<ControlServer>,
 }
 
 impl App {
-    pub fn new(g: GlGraphics) -> App {
+    const SPEEDS: [f64; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+    const NORMAL_SPEED: usize = 2;
+    const INDICATOR_SIZE: u32 = 16;
+
+    pub fn new(g: GlGraphics, sim: Simulation, input: InputMap) -> App {
         App {
             glyphs: GlyphCache::new("./assets/FiraSans-Regular.ttf", (), TextureSettings::new())
                 .expect("Unable to load font"),
             gl: g,
-            grid: Grid::new(),
-            cannons: [
-                Cannon::new(1, colors::PLAYER1_CANNON, true, true),
-                Cannon::new(2, colors::PLAYER2_CANNON, false, true),
-                Cannon::new(3, colors::PLAYER3_CANNON, true, false),
-                Cannon::new(4, colors::PLAYER4_CANNON, false, false),
-            ],
-            bullets: Vec::new(),
-            field_rect: [0.0, 0.0, 10.0, 10.0],
-            plinkos: [
-                Plinko::new(
-                    1,
-                    colors::PLAYER1_CANNON,
-                    [common::BORDER_SIZE as f64, common::BORDER_SIZE as f64],
-                ),
-                Plinko::new(
-                    2,
-                    colors::PLAYER2_CANNON,
-                    [
-                        (common::BORDER_SIZE * 3
-                            + (common::CELL_EDGES * common::CELL_WIDTH * 2)
-                            + common::SIDE_WIDTH) as f64,
-                        common::BORDER_SIZE as f64,
-                    ],
-                ),
-                Plinko::new(
-                    3,
-                    colors::PLAYER3_CANNON,
-                    [
-                        common::BORDER_SIZE as f64,
-                        (common::BORDER_SIZE + (common::CELL_EDGES * common::CELL_WIDTH)) as f64,
-                    ],
-                ),
-                Plinko::new(
-                    4,
-                    colors::PLAYER4_CANNON,
-                    [
-                        (common::BORDER_SIZE * 3
-                            + (common::CELL_EDGES * common::CELL_WIDTH * 2)
-                            + common::SIDE_WIDTH) as f64,
-                        (common::BORDER_SIZE + (common::CELL_EDGES * common::CELL_WIDTH)) as f64,
-                    ],
-                ),
-            ],
+            sim,
+            field: FieldTexture::new(),
+            debug: DebugOverlay::new(),
+            paused: false,
+            step_requested: false,
+            speed_index: App::NORMAL_SPEED,
+            pending_ticks: 0.0,
+            view: View::fit([layout::WIDTH as f64, layout::HEIGHT as f64]),
+            cursor: [0.0, 0.0],
+            mouse_seat: None,
+            input,
+            screenshot_requested: false,
+            snapshot_path: "snapshot.json".to_string(),
+            grid_path: "grid.png".to_string(),
+            rewind: Rewind::new(Rewind::DEFAULT_SECONDS),
+            is_seeking: false,
+            lockstep: None,
+            recorder: None,
+            replay: None,
+            highlights: None,
+            spectators: None,
+            chat: None,
+            control: None,
         }
     }
 
     pub const fn get_width() -> i32 {
-        let grid_size = common::CELL_WIDTH * common::CELL_EDGES * 2;
-        return grid_size + (4 * common::BORDER_SIZE) + (2 * common::SIDE_WIDTH);
+        layout::WIDTH
     }
     pub const fn get_height() -> i32 {
-        let grid_size = common::CELL_WIDTH * common::CELL_EDGES * 2;
-        return grid_size + (2 * common::BORDER_SIZE);
+        layout::HEIGHT
     }
 
     pub fn render(&mut self, args: &RenderArgs) {
+        self.debug.record_frame();
+        self.field.update(&mut self.sim.grid);
+        let view = View::fit(args.window_size);
+        self.view = view;
+        // the clock state on the left of the frame above the field, chat cooldowns in the middle
+        let hud = [
+            (0.0, self.clock_indicator()),
+            (0.5, self.chat.as_ref().map(ChatClient::status)),
+        ];
         self.gl.draw(args.viewport(), |c, gl| {
-            fn draw_full_column(
-                c: &graphics::Context,
-                gl: &mut GlGraphics,
-                x: i32,
-                height: i32,
-            ) -> i32 {
-                let rect = [x as f64, 0.0, common::BORDER_SIZE as f64, height as f64];
-                graphics::rectangle(graphics::color::hex(colors::FRAME), rect, c.transform, gl);
-                return x + common::BORDER_SIZE;
-            }
-
-            fn draw_full_row(
-                c: &graphics::Context,
-                gl: &mut GlGraphics,
-                y: i32,
-                width: i32,
-            ) -> i32 {
-                let rect = [0.0, y as f64, width as f64, common::BORDER_SIZE as f64];
-                graphics::rectangle(graphics::color::hex(colors::FRAME), rect, c.transform, gl);
-                return y + common::BORDER_SIZE;
-            }
-
-            // Clear the screen.
+            // Clear the screen, which also letterboxes the layout when the aspect ratio differs.
             graphics::clear(graphics::color::hex(colors::BACKGROUND), gl);
 
-            let window_width = App::get_width();
-            let window_height = App::get_height();
-
-            let mut current_x = draw_full_column(&c, gl, 0, window_height);
-            current_x = draw_full_column(&c, gl, current_x + common::SIDE_WIDTH, window_height);
-            let grid_left = current_x;
-            let grid_right = grid_left + (common::CELL_WIDTH * common::CELL_EDGES * 2);
-            let grid_top = draw_full_row(&c, gl, 0, window_width);
-            let grid_bottom = grid_top + (common::CELL_WIDTH * common::CELL_EDGES * 2);
-            self.field_rect = [
-                grid_left as f64,
-                grid_top as f64,
-                (grid_right - grid_left) as f64,
-                (grid_bottom - grid_top) as f64,
-            ];
-            for i in 0..(common::CELL_EDGES * 2) {
-                let x = common::CELL_WIDTH + current_x;
-                let mut current_y = grid_top;
-                for j in 0..(common::CELL_EDGES * 2) {
-                    let y = common::CELL_WIDTH + current_y;
-                    let rect = [
-                        current_x as f64,
-                        current_y as f64,
-                        common::CELL_WIDTH as f64,
-                        common::CELL_WIDTH as f64,
-                    ];
-                    let color = match self.grid.cells[common::calc_logical_index(i, j)] {
-                        1 => graphics::color::hex(colors::PLAYER1_FIELD),
-                        2 => graphics::color::hex(colors::PLAYER2_FIELD),
-                        3 => graphics::color::hex(colors::PLAYER3_FIELD),
-                        4 => graphics::color::hex(colors::PLAYER4_FIELD),
-                        _ => graphics::color::hex(colors::BACKGROUND),
-                    };
-                    graphics::rectangle(color, rect, c.transform, gl);
-                    current_y = y;
-                }
-                let xline = [x as f64, grid_top as f64, x as f64, grid_bottom as f64];
-                graphics::line(
-                    graphics::color::hex(colors::GRID),
-                    1.0,
-                    xline,
-                    c.transform,
-                    gl,
-                );
-                current_x = x;
-            }
-
-            for y in 0..(common::CELL_EDGES * 2) {
-                let axis = (grid_top + (y * common::CELL_WIDTH)) as f64;
-                let yline = [grid_left as f64, axis, grid_right as f64, axis];
-                graphics::line(
-                    graphics::color::hex(colors::GRID),
-                    1.0,
-                    yline,
-                    c.transform,
-                    gl,
-                );
+            let c = graphics::Context {
+                transform: view.transform(c.transform),
+                ..c
+            };
+            for rect in layout::frame_rects() {
+                graphics::rectangle(graphics::color::hex(colors::FRAME), rect, c.transform, gl);
             }
 
-            current_x = draw_full_column(&c, gl, current_x, window_height);
-            draw_full_column(&c, gl, current_x + common::SIDE_WIDTH, window_height);
-            draw_full_row(&c, gl, grid_bottom, window_width);
+            // the territory is cached in a texture that only changes where cells were captured
+            let field_rect = self.sim.field_rect();
+            graphics::image(
+                self.field.texture(),
+                c.transform.trans(field_rect[0], field_rect[1]),
+                gl,
+            );
 
-            for cannon in &mut self.cannons {
+            for cannon in &self.sim.cannons {
                 cannon.draw(&c, gl);
             }
-            for bullet in &mut self.bullets {
+            for bullet in &self.sim.bullets {
                 bullet.draw(&c, gl);
             }
-            for plinko in &mut self.plinkos {
+            for plinko in &self.sim.plinkos {
                 plinko.draw(&c, gl, &mut self.glyphs);
             }
+            if self.debug.is_visible {
+                self.debug.draw(&self.sim, &c, gl, &mut self.glyphs);
+            }
+
+            for (offset, text) in &hud {
+                let Some(text) = text else { continue };
+                let size = App::INDICATOR_SIZE as f64;
+                let text_transform = c.transform.trans(
+                    field_rect[0] + field_rect[2] * offset,
+                    (common::BORDER_SIZE as f64 - size) / 2.0 + size * 0.85,
+                );
+                Text::new_color(graphics::color::hex(colors::HUD), App::INDICATOR_SIZE)
+                    .draw(text, &mut self.glyphs, &c.draw_state, text_transform, gl)
+                    .expect("Unable to render text");
+            }
+            self.rewind.draw(&c, gl);
+            if let Some(replay) = &self.replay {
+                replay.draw(&c, gl);
+            }
         });
+
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            match screenshot::save(args.draw_size) {
+                Ok(path) => println!("Saved screenshot to {}", path),
+                Err(e) => eprintln!("Unable to save screenshot: {}", e),
+            }
+        }
+    }
+
+    /// Shows the clock state whenever it is not running normally, or the state of a LAN game.
+    fn clock_indicator(&self) -> Option<String> {
+        if let Some(lockstep) = &self.lockstep {
+            let status = lockstep.status().unwrap_or("in sync");
+            return Some(format!("LAN player {}: {}", lockstep.seat(), status));
+        }
+        if self.rewind.is_rewinding() {
+            return Some(format!(
+                "REWIND -{:.1}s (space to play on)",
+                self.rewind.seconds_back()
+            ));
+        }
+        let speed = App::SPEEDS[self.speed_index];
+        let clock = if self.paused {
+            Some(format!("PAUSED (x{})", speed))
+        } else if self.speed_index != App::NORMAL_SPEED {
+            Some(format!("x{}", speed))
+        } else {
+            None
+        };
+        match (&self.replay, clock) {
+            (Some(replay), Some(clock)) => Some(format!("{}  {}", replay.status(), clock)),
+            (Some(replay), None) => Some(replay.status()),
+            (None, clock) => clock,
+        }
     }
 
     pub fn update(&mut self, args: &UpdateArgs) {
-        // move bullets
-        for bullet in &mut self.bullets {
-            bullet.step(self.field_rect);
-            if self.grid.check_collision(
-                bullet.position[0] - self.field_rect[0],
-                bullet.position[1] - self.field_rect[1],
-                bullet.cannon_id,
-            ) {
-                bullet.is_alive = false;
-            }
-            for cannon in &mut self.cannons {
-                cannon.check_collision(bullet);
-                if !cannon.is_alive {
-                    self.plinkos[(cannon.id - 1) as usize].is_alive = false;
+        let start = Instant::now();
+        if self.rewind.is_rewinding() {
+            // the match stands still on the frame on show until it is played on
+            self.debug.record_update(start.elapsed());
+            return;
+        }
+        if let Some(control) = &mut self.control {
+            control.handle(&mut self.sim, &mut self.paused);
+        }
+        // before the ticks, like keys and the control API, so that a recording has what chat
+        // does in the next tick and not also in a keyframe written after them
+        if let Some(chat) = &mut self.chat {
+            chat.apply(&mut self.sim);
+        }
+        // everyone in a LAN game plays at the same pace, so the clock controls do not apply
+        let due_ticks = match self.lockstep {
+            Some(_) => 0,
+            None => self.due_ticks(),
+        };
+        if let Some(replay) = &mut self.replay {
+            for _ in 0..due_ticks {
+                replay.advance(&mut self.sim);
+            }
+            self.debug.record_update(start.elapsed());
+            return;
+        }
+        // highlights look at every tick, or a bullet can pass a cannon between looks when several
+        // ticks run at once, at high speed or as they arrive in a LAN game
+        match &mut self.lockstep {
+            Some(lockstep) => {
+                let highlights = &mut self.highlights;
+                lockstep.advance(&mut self.sim, 1, |sim| {
+                    if let Some(highlights) = highlights {
+                        highlights.observe(sim);
+                    }
+                });
+            }
+            None => {
+                for _ in 0..due_ticks {
+                    self.sim.update(args.dt);
+                    if let Some(highlights) = &mut self.highlights {
+                        highlights.observe(&self.sim);
+                    }
                 }
             }
         }
-        self.bullets.retain(|b| b.is_alive);
+        if self.lockstep.is_none() {
+            self.rewind.record(&self.sim);
+        }
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(message) = recorder.record(&mut self.sim, args.dt) {
+                eprintln!("{}, recording stopped", message);
+                self.recorder = None;
+            }
+        }
+        if let Some(spectators) = &mut self.spectators {
+            spectators.publish(&self.sim);
+        }
+        self.debug.record_update(start.elapsed());
+    }
 
-        // check plinkos for updates
-        let mut updates: Vec<PlinkoEvent> = Vec::new();
-        for plinko in &mut self.plinkos {
-            let func = |event: PlinkoEvent| {
-                updates.push(event);
-            };
-            plinko.update(args.dt, func);
+    /// Ticks to play in this update, as the clock controls say.
+    fn due_ticks(&mut self) -> usize {
+        if self.paused {
+            return std::mem::take(&mut self.step_requested) as usize;
+        }
+        // bullets, cannons and plinkos all advance by whole ticks, so scaling time means
+        // running more or fewer ticks per update
+        self.pending_ticks += App::SPEEDS[self.speed_index];
+        let ticks = self.pending_ticks.floor();
+        self.pending_ticks -= ticks;
+        ticks as usize
+    }
+
+    /// Performs the action bound to a pressed button and returns it, so the caller can handle
+    /// actions that concern the window rather than the game.
+    pub fn handle_button(&mut self, button: &Button) -> Option<Action> {
+        let action = self.input.action(button)?;
+        if self.lockstep.is_some() && App::changes_match(action) {
+            return Some(action);
+        }
+        if self.replay.is_some() {
+            self.handle_replay_action(action);
+            return Some(action);
         }
-        for update in updates {
-            self.update_callback(update);
+        if self.rewind.is_rewinding() {
+            self.handle_rewind_action(action);
+            return Some(action);
+        }
+        match action {
+            Action::LoadShots(id) => {
+                self.load_cannon(id, 16);
+            }
+            Action::Pause => {
+                self.paused = !self.paused;
+                self.pending_ticks = 0.0;
+            }
+            Action::Step => {
+                self.step_requested = self.paused;
+            }
+            Action::SlowDown => {
+                self.speed_index = self.speed_index.saturating_sub(1);
+            }
+            Action::SpeedUp => {
+                self.speed_index = (self.speed_index + 1).min(App::SPEEDS.len() - 1);
+            }
+            Action::ToggleDebug => {
+                self.debug.is_visible = !self.debug.is_visible;
+            }
+            Action::RotateCcw(id) | Action::RotateCw(id) | Action::Fire(id) => {
+                self.handle_seat_action(id, action, true);
+            }
+            Action::Click => {
+                self.handle_click();
+            }
+            Action::Screenshot => {
+                self.screenshot_requested = true;
+            }
+            Action::Restart => {
+                self.sim.restart(rand::random());
+                self.rewind.clear();
+            }
+            Action::SaveSnapshot => match self.sim.save_snapshot(&self.snapshot_path) {
+                Ok(()) => println!("Saved snapshot to {}", self.snapshot_path),
+                Err(message) => eprintln!("{}", message),
+            },
+            Action::LoadSnapshot => match Simulation::load_snapshot(&self.snapshot_path) {
+                Ok(sim) => {
+                    self.sim = sim;
+                    self.rewind.clear();
+                }
+                Err(message) => eprintln!("{}", message),
+            },
+            Action::SaveGrid => self.save_grid(),
+            Action::Rewind => {
+                if let Some(sim) = self.rewind.start(&self.sim) {
+                    self.sim = sim;
+                }
+            }
+            Action::ToggleFullscreen | Action::NextBookmark | Action::PreviousBookmark => {}
+        }
+        Some(action)
+    }
+
+    /// While rewinding, the rewind key goes back a second, step goes forward a tenth, the
+    /// timeline bar seeks and pause plays on from the frame on show. The match can't be
+    /// changed otherwise.
+    fn handle_rewind_action(&mut self, action: Action) {
+        let shown = match action {
+            Action::Rewind => self.rewind.step(-(Rewind::FRAMES_PER_SECOND as isize)),
+            Action::Step => self.rewind.step(1),
+            Action::Click if self.rewind.is_on_bar(self.cursor) => {
+                self.is_seeking = true;
+                self.rewind.seek(self.cursor[0])
+            }
+            Action::Pause => {
+                match self.rewind.resume() {
+                    Some(Ok(sim)) => self.sim = sim,
+                    Some(Err(message)) => eprintln!("{}", message),
+                    None => {}
+                }
+                self.paused = false;
+                self.pending_ticks = 0.0;
+                None
+            }
+            Action::ToggleDebug => {
+                self.debug.is_visible = !self.debug.is_visible;
+                None
+            }
+            Action::Screenshot => {
+                self.screenshot_requested = true;
+                None
+            }
+            Action::SaveGrid => {
+                self.save_grid();
+                None
+            }
+            _ => None,
+        };
+        if let Some(sim) = shown {
+            self.sim = sim;
         }
+    }
 
-        // rotate cannons and fire next round of bullets
-        for cannon in &mut self.cannons {
-            if let Some(b) = cannon.shoot() {
-                self.bullets.push(b);
+    /// While watching a recording, the clock controls play it, the timeline bar and the
+    /// bookmark keys seek, and nothing else changes the match.
+    fn handle_replay_action(&mut self, action: Action) {
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+        let shown = match action {
+            Action::Pause if self.paused && replay.is_over() => {
+                // playing a finished recording watches it again
+                self.paused = false;
+                Some(replay.seek_to(0))
+            }
+            Action::Pause => {
+                self.paused = !self.paused;
+                self.pending_ticks = 0.0;
+                None
+            }
+            Action::Step => {
+                self.step_requested = self.paused;
+                None
+            }
+            Action::SlowDown => {
+                self.speed_index = self.speed_index.saturating_sub(1);
+                None
             }
-            cannon.turn();
+            Action::SpeedUp => {
+                self.speed_index = (self.speed_index + 1).min(App::SPEEDS.len() - 1);
+                None
+            }
+            Action::Click if timeline::is_on_bar(self.cursor) => {
+                self.is_seeking = true;
+                Some(replay.seek(self.cursor[0]))
+            }
+            Action::NextBookmark => replay.jump_to_bookmark(true),
+            Action::PreviousBookmark => replay.jump_to_bookmark(false),
+            Action::ToggleDebug => {
+                self.debug.is_visible = !self.debug.is_visible;
+                None
+            }
+            Action::Screenshot => {
+                self.screenshot_requested = true;
+                None
+            }
+            _ => None,
+        };
+        if let Some(sim) = shown {
+            self.sim = sim;
         }
     }
 
-    fn update_callback(&mut self, event: PlinkoEvent) {
-        self.load_cannon(event.id, event.num_shots);
+    fn save_grid(&self) {
+        match self.sim.grid.save_image(&self.grid_path) {
+            Ok(()) => println!("Saved grid to {}", self.grid_path),
+            Err(message) => eprintln!("{}", message),
+        }
     }
 
-    pub fn handle_button(&mut self, button: &Button) {
-        match button {
-            Button::Keyboard(key) => match key {
-                Key::D1 => {
-                    self.load_cannon(1, 16);
-                }
-                Key::D2 => {
-                    self.load_cannon(2, 16);
+    /// Actions that change the match for everyone, which a single player of a LAN game can't do.
+    fn changes_match(action: Action) -> bool {
+        matches!(
+            action,
+            Action::LoadShots(_)
+                | Action::Pause
+                | Action::Step
+                | Action::SlowDown
+                | Action::SpeedUp
+                | Action::Restart
+                | Action::LoadSnapshot
+                | Action::Rewind
+        )
+    }
+
+    /// Plays a LAN match, where this machine only controls its own seat, with the mouse too if
+    /// it was given a seat.
+    pub fn set_lockstep(&mut self, lockstep: Lockstep) {
+        let seat = (lockstep.seat() - 1) as usize;
+        self.mouse_seat = self.mouse_seat.map(|_| seat);
+        self.lockstep = Some(lockstep);
+    }
+
+    /// Saves and loads snapshots of the match in `path` rather than `snapshot.json`.
+    pub fn set_snapshot_path(&mut self, path: String) {
+        self.snapshot_path = path;
+    }
+
+    /// Saves the grid as an image in `path` rather than `grid.png`, see `Grid::save_image`.
+    pub fn set_grid_path(&mut self, path: String) {
+        self.grid_path = path;
+    }
+
+    /// Keeps the last `seconds` of play to rewind through, none when 0.
+    pub fn set_rewind_seconds(&mut self, seconds: f64) {
+        self.rewind = Rewind::new(seconds);
+    }
+
+    /// Writes the match to a recording while it is played, see `Recorder`.
+    pub fn set_recorder(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Prints the highlights of the match as they are played.
+    pub fn set_highlights(&mut self, highlights: Highlights) {
+        self.highlights = Some(highlights);
+    }
+
+    /// Watches a recorded match instead of playing one, starting from `Replay::open`.
+    pub fn set_replay(&mut self, replay: Replay) {
+        self.replay = Some(replay);
+    }
+
+    /// Lets an IRC channel take part in the match.
+    pub fn set_chat(&mut self, chat: ChatClient) {
+        self.chat = Some(chat);
+    }
+
+    /// Answers the HTTP control API between updates.
+    pub fn set_control(&mut self, control: ControlServer) {
+        self.control = Some(control);
+    }
+
+    /// Streams the match to spectators while it is played.
+    pub fn set_spectators(&mut self, spectators: SpectatorServer) {
+        self.spectators = Some(spectators);
+    }
+
+    pub fn handle_release(&mut self, button: &Button) {
+        match self.input.action(button) {
+            Some(action @ (Action::RotateCcw(id) | Action::RotateCw(id) | Action::Fire(id))) => {
+                self.handle_seat_action(id, action, false);
+            }
+            Some(Action::Click) => {
+                self.is_seeking = false;
+                if let Some(manual) = self.mouse_manual() {
+                    manual.is_firing = false;
                 }
-                Key::D3 => {
-                    self.load_cannon(3, 16);
+            }
+            _ => {}
+        }
+    }
+
+    /// Lets the mouse drive a player's cannon: it follows the cursor and fires while the left
+    /// button is held.
+    pub fn set_mouse_seat(&mut self, cannon_id: i8) {
+        self.sim.set_human(cannon_id);
+        self.mouse_seat = Some((cannon_id - 1) as usize);
+    }
+
+    pub fn handle_cursor(&mut self, position: [f64; 2]) {
+        self.cursor = self.view.to_logical(position);
+        let cursor = self.cursor;
+        if self.is_seeking {
+            let shown = match &mut self.replay {
+                Some(replay) => Some(replay.seek(cursor[0])),
+                None => self.rewind.seek(cursor[0]),
+            };
+            if let Some(sim) = shown {
+                self.sim = sim;
+            }
+            return;
+        }
+        if let Some(manual) = self.mouse_manual() {
+            manual.target = Some(cursor);
+        }
+    }
+
+    fn handle_click(&mut self) {
+        // clicking the top of a person's plinko board picks where their next puck drops
+        for i in 0..self.sim.plinkos.len() {
+            let [x, y, width, height] = self.sim.plinkos[i].get_drop_rect();
+            let [cx, cy] = self.cursor;
+            let is_own = match &self.lockstep {
+                Some(lockstep) => lockstep.seat() == (i + 1) as i8,
+                None => self.sim.cannons[i].manual.is_some(),
+            };
+            if is_own && cx >= x && cx <= x + width && cy >= y && cy <= y + height {
+                match &mut self.lockstep {
+                    Some(lockstep) => lockstep.local_input().drop_x = Some(cx),
+                    None => self.sim.choose_drop((i + 1) as i8, cx),
                 }
-                Key::D4 => {
-                    self.load_cannon(4, 16);
+                return;
+            }
+        }
+        if let Some(manual) = self.mouse_manual() {
+            manual.is_firing = true;
+        }
+    }
+
+    fn mouse_manual(&mut self) -> Option<&mut ManualControl> {
+        let seat = self.mouse_seat?;
+        match &mut self.lockstep {
+            Some(lockstep) => Some(&mut lockstep.local_input().manual),
+            None => self.sim.cannons[seat].manual.as_mut(),
+        }
+    }
+
+    /// Updates the held controls of a player's cannon. Pressing one of them hands that cannon
+    /// over to the person at the keyboard.
+    fn handle_seat_action(&mut self, cannon_id: i8, action: Action, is_pressed: bool) {
+        let manual = match &mut self.lockstep {
+            // in a LAN match only the seat of this machine can be driven
+            Some(lockstep) if lockstep.seat() == cannon_id => &mut lockstep.local_input().manual,
+            Some(_) => return,
+            None => {
+                let cannon = &mut self.sim.cannons[(cannon_id - 1) as usize];
+                if !cannon.is_alive || (!is_pressed && cannon.manual.is_none()) {
+                    return;
                 }
-                _ => {}
-            },
-            _ => {}
+                cannon.manual.get_or_insert_with(ManualControl::default)
+            }
+        };
+        match action {
+            Action::RotateCcw(_) => manual.is_turning_ccw = is_pressed,
+            Action::RotateCw(_) => manual.is_turning_cw = is_pressed,
+            _ => manual.is_firing = is_pressed,
         }
     }
 
     fn load_cannon(&mut self, cannon_id: i8, num_shots: i32) {
-        self.cannons[(cannon_id - 1) as usize].load(num_shots);
+        self.sim.load_cannon(cannon_id, num_shots);
     }
 }
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use super::cannon::Cannon;
+use super::common;
+use super::plinko::Plinko;
+
+/// The shape of the plinko boards.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlinkoGeometry {
+    // pins in the widest rows; the rows between have one fewer
+    pub pin_columns: i32,
+    // the pins stand on the lines between this many equal bands
+    pub pin_rows: i32,
+    // starting width of the multiplier well
+    pub well_width: f64,
+    // boards on the right panel have their multiplier well on the left, facing the field
+    pub mirror_right: bool,
+}
+
+impl Default for PlinkoGeometry {
+    fn default() -> PlinkoGeometry {
+        PlinkoGeometry {
+            pin_columns: 3,
+            pin_rows: 5,
+            well_width: Plinko::MIN_WELL_WIDTH,
+            mirror_right: false,
+        }
+    }
+}
+
+/// Everything about the field that a match starts from, which the default describes as the
+/// game has always been played. Any part left out of a file keeps its default.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Arena {
+    // cells along the middle of the field, both across and down, that start out neutral
+    pub neutral_band: i32,
+    // distance of each cannon from its corner of the field, along both edges
+    pub cannon_inset: f64,
+    // degrees either side of the diagonal that a cannon can turn
+    pub sweep: f64,
+    pub plinko: PlinkoGeometry,
+}
+
+impl Default for Arena {
+    fn default() -> Arena {
+        Arena {
+            neutral_band: 0,
+            cannon_inset: (Cannon::RADIUS * 3 / 2) as f64,
+            sweep: Cannon::SWEEP,
+            plinko: PlinkoGeometry::default(),
+        }
+    }
+}
+
+impl Arena {
+    // pins closer together than this leave no room for the pucks, see `Puck::RADIUS`
+    const MAX_PIN_COLUMNS: i32 = 6;
+    const MAX_PIN_ROWS: i32 = 12;
+
+    pub fn load(path: &str) -> Result<Arena, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read arena '{}': {}", path, e))?;
+        let arena: Arena = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid arena in '{}': {}", path, e))?;
+        arena
+            .check()
+            .map_err(|e| format!("Invalid arena in '{}': {}", path, e))?;
+        Ok(arena)
+    }
+
+    /// Fails with the first setting the field can't be built with.
+    fn check(&self) -> Result<(), String> {
+        let cells = common::CELL_EDGES * 2;
+        if !(0..=cells).contains(&self.neutral_band) {
+            return Err(format!("neutral_band must be 0 to {}", cells));
+        }
+        // each cannon stays within its own quarter of the field
+        let max_inset = (common::CELL_EDGES * common::CELL_WIDTH - Cannon::RADIUS) as f64;
+        if !(Cannon::RADIUS as f64..=max_inset).contains(&self.cannon_inset) {
+            return Err(format!(
+                "cannon_inset must be {} to {}",
+                Cannon::RADIUS,
+                max_inset
+            ));
+        }
+        if !(self.sweep > 0.0 && self.sweep <= 180.0) {
+            return Err("sweep must be more than 0 and at most 180".to_string());
+        }
+        let plinko = &self.plinko;
+        if !(1..=Arena::MAX_PIN_COLUMNS).contains(&plinko.pin_columns) {
+            return Err(format!(
+                "pin_columns must be 1 to {}",
+                Arena::MAX_PIN_COLUMNS
+            ));
+        }
+        if !(2..=Arena::MAX_PIN_ROWS).contains(&plinko.pin_rows) {
+            return Err(format!("pin_rows must be 2 to {}", Arena::MAX_PIN_ROWS));
+        }
+        if !(plinko.well_width > 0.0 && plinko.well_width <= Plinko::max_well_width()) {
+            return Err(format!(
+                "well_width must be more than 0 and at most {}",
+                Plinko::max_well_width()
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+use std::thread;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::Simulation;
+
+/// The eight ways of seating the players that turn or flip the square field, where player `i`
+/// sits at seat `ARRANGEMENTS[k][i - 1]`. Seats 1 and 2 are the top corners, left first.
+pub const ARRANGEMENTS: [[i8; 4]; 8] = [
+    [1, 2, 3, 4],
+    // turned a quarter clockwise, half and three quarters
+    [2, 4, 1, 3],
+    [4, 3, 2, 1],
+    [3, 1, 4, 2],
+    // flipped left to right, top to bottom, and across each diagonal
+    [2, 1, 4, 3],
+    [3, 4, 1, 2],
+    [1, 3, 2, 4],
+    [4, 2, 3, 1],
+];
+
+/// How a balance study is run.
+pub struct Study {
+    // rounds of one match in every arrangement
+    pub rounds: usize,
+    pub seed: u64,
+    pub delta_time: f64,
+    // matches that last longer are scored as they stand
+    pub max_ticks: u64,
+}
+
+/// How one match ended, by seat.
+struct Outcome {
+    territory: [usize; 4],
+    survived: [bool; 4],
+    winner: usize,
+}
+
+/// What a study found about each seat.
+pub struct Balance {
+    matches: usize,
+    wins: [usize; 4],
+    survivals: [usize; 4],
+    territory_mean: [f64; 4],
+    // standard error of each mean
+    territory_error: [f64; 4],
+    territory_z: [f64; 4],
+}
+
+// a seat whose mean territory is this many standard errors from the average is flagged, which
+// happens by chance to one of four fair seats about one study in twenty
+const Z_LIMIT: f64 = 2.5;
+const SIGNIFICANCE: f64 = 0.05;
+
+/// Plays rounds of matches with the players in each of `ARRANGEMENTS`, using `new_match` to
+/// set up a match with the players in the given seats.
+///
+/// Each player sits in each seat equally often, so how strong the players are cancels out and
+/// any difference left between the seats comes from the field.
+pub fn study<F>(study: &Study, new_match: F) -> Result<Balance, String>
+where
+    F: Fn(u64, [i8; 4]) -> Result<Simulation, String> + Sync,
+{
+    let mut rng = StdRng::seed_from_u64(study.seed);
+    // every match has a seed of its own, so that no two of them repeat each other when the
+    // players are alike
+    let jobs = (0..study.rounds)
+        .flat_map(|_| ARRANGEMENTS)
+        .map(|seats| (rng.gen(), seats))
+        .collect::<Vec<(u64, [i8; 4])>>();
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let group = jobs.len().div_ceil(threads).max(1);
+    let new_match = &new_match;
+    let outcomes = thread::scope(|scope| {
+        let handles = jobs
+            .chunks(group)
+            .map(|jobs| {
+                scope.spawn(move || {
+                    jobs.iter()
+                        .map(|(seed, seats)| new_match(*seed, *seats).map(|sim| play(study, sim)))
+                        .collect::<Result<Vec<Outcome>, String>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut outcomes = Vec::with_capacity(jobs.len());
+        for handle in handles {
+            outcomes.extend(handle.join().expect("A match panicked")?);
+        }
+        Ok::<_, String>(outcomes)
+    })?;
+    Ok(Balance::from_outcomes(&outcomes))
+}
+
+fn play(study: &Study, mut sim: Simulation) -> Outcome {
+    while sim.alive_count() > 1 && sim.tick() < study.max_ticks {
+        sim.update(study.delta_time);
+    }
+    let territory = [1, 2, 3, 4].map(|id| sim.grid.territory(id));
+    let survived = [0, 1, 2, 3].map(|i| sim.cannons[i].is_alive);
+    // the last one standing, or whoever holds the most cells when time runs out or the last
+    // cannons fall together
+    let is_contender = |i: &usize| survived[*i] || !survived.contains(&true);
+    let winner = (0..4)
+        .filter(is_contender)
+        .max_by_key(|i| territory[*i])
+        .expect("Someone holds the most cells");
+    Outcome {
+        territory,
+        survived,
+        winner,
+    }
+}
+
+impl Balance {
+    fn from_outcomes(outcomes: &[Outcome]) -> Balance {
+        let n = outcomes.len().max(1) as f64;
+        let mut wins = [0; 4];
+        let mut survivals = [0; 4];
+        let mut territory_mean = [0.0; 4];
+        for outcome in outcomes {
+            wins[outcome.winner] += 1;
+            for seat in 0..4 {
+                survivals[seat] += outcome.survived[seat] as usize;
+                territory_mean[seat] += outcome.territory[seat] as f64 / n;
+            }
+        }
+        let mut variance = [0.0; 4];
+        for outcome in outcomes {
+            for seat in 0..4 {
+                variance[seat] +=
+                    (outcome.territory[seat] as f64 - territory_mean[seat]).powi(2) / n;
+            }
+        }
+        let grand_mean = territory_mean.iter().sum::<f64>() / 4.0;
+        let pooled_error = (variance.iter().sum::<f64>() / 4.0 / n).sqrt();
+        Balance {
+            matches: outcomes.len(),
+            wins,
+            survivals,
+            territory_mean,
+            territory_error: variance.map(|v| (v / n).sqrt()),
+            territory_z: territory_mean.map(|mean| {
+                if pooled_error > 0.0 {
+                    (mean - grand_mean) / pooled_error
+                } else {
+                    0.0
+                }
+            }),
+        }
+    }
+
+    /// The table of seats followed by the tests and a verdict.
+    pub fn report(&self) -> Vec<String> {
+        let mut lines = vec!["seat  wins   win%  survived  territory         z".to_string()];
+        for seat in 0..4 {
+            lines.push(format!(
+                "{:>4}  {:>4}  {:>5.1}  {:>8}  {:>7.1} ± {:<5.1}  {:>5.2}",
+                seat + 1,
+                self.wins[seat],
+                100.0 * self.wins[seat] as f64 / self.matches.max(1) as f64,
+                self.survivals[seat],
+                self.territory_mean[seat],
+                1.96 * self.territory_error[seat],
+                self.territory_z[seat]
+            ));
+        }
+
+        let mut findings = Vec::new();
+        for (name, counts) in [("wins", self.wins), ("survivals", self.survivals)] {
+            let (statistic, p) = uniformity(counts);
+            lines.push(format!(
+                "{} chi-square {:.2} (3 df), p = {:.3}",
+                name, statistic, p
+            ));
+            if p < SIGNIFICANCE {
+                findings.push(format!("{} differ between seats", name));
+            }
+        }
+        for seat in 0..4 {
+            let z = self.territory_z[seat];
+            if z.abs() > Z_LIMIT {
+                let side = if z > 0.0 { "more" } else { "less" };
+                findings.push(format!(
+                    "seat {} holds {:.1} standard errors {} territory than average",
+                    seat + 1,
+                    z.abs(),
+                    side
+                ));
+            }
+        }
+        if findings.is_empty() {
+            lines.push(format!(
+                "No seat is favored at the {}% level over {} matches",
+                SIGNIFICANCE * 100.0,
+                self.matches
+            ));
+        } else {
+            for finding in findings {
+                lines.push(format!("Imbalance: {}", finding));
+            }
+        }
+        lines
+    }
+}
+
+/// Pearson's chi-square test of whether four counts are equally likely, returning the
+/// statistic and its p-value.
+fn uniformity(counts: [usize; 4]) -> (f64, f64) {
+    let total = counts.iter().sum::<usize>() as f64;
+    if total == 0.0 {
+        return (0.0, 1.0);
+    }
+    let expected = total / 4.0;
+    let statistic = counts
+        .iter()
+        .map(|c| (*c as f64 - expected).powi(2) / expected)
+        .sum::<f64>();
+    (statistic, p_value(statistic))
+}
+
+/// The chance of a chi-square statistic at least this large with 3 degrees of freedom, which
+/// has a closed form.
+fn p_value(statistic: f64) -> f64 {
+    let p = erfc((statistic / 2.0).sqrt())
+        + (2.0 * statistic / std::f64::consts::PI).sqrt() * (-statistic / 2.0).exp();
+    p.clamp(0.0, 1.0)
+}
+
+/// The complementary error function, to within about 1e-7 (Abramowitz and Stegun 7.1.26).
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x);
+    let poly = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    poly * (-x * x).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrangements_are_distinct_permutations_of_the_seats() {
+        for arrangement in ARRANGEMENTS {
+            let mut seats = arrangement;
+            seats.sort();
+            assert_eq!(seats, [1, 2, 3, 4]);
+        }
+        for (i, a) in ARRANGEMENTS.iter().enumerate() {
+            assert!(ARRANGEMENTS[i + 1..].iter().all(|b| a != b));
+        }
+    }
+
+    #[test]
+    fn p_value_matches_chi_square_table() {
+        // critical values of the chi-square distribution with 3 degrees of freedom
+        assert!((p_value(7.815) - 0.05).abs() < 1e-4);
+        assert!((p_value(11.345) - 0.01).abs() < 1e-4);
+        assert!((p_value(0.584) - 0.9).abs() < 1e-4);
+        assert!((p_value(0.0) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn uniformity_of_counts() {
+        assert_eq!(uniformity([0; 4]), (0.0, 1.0));
+        let (statistic, p) = uniformity([10; 4]);
+        assert_eq!(statistic, 0.0);
+        assert!((p - 1.0).abs() < 1e-6);
+        let (statistic, p) = uniformity([16, 10, 10, 4]);
+        assert!((statistic - 7.2).abs() < 1e-9);
+        assert!(p > SIGNIFICANCE && p < 0.07);
+    }
+
+    #[test]
+    fn erfc_matches_known_values() {
+        assert!((erfc(0.0) - 1.0).abs() < 1e-6);
+        assert!((erfc(0.5) - 0.479_500_1).abs() < 1e-6);
+        assert!((erfc(1.0) - 0.157_299_2).abs() < 1e-6);
+        assert!((erfc(2.0) - 0.004_677_7).abs() < 1e-6);
+    }
+}
@@ -1,6 +1,8 @@
 use graphics::Radians;
 use opengl_graphics::GlGraphics;
+use serde::{Deserialize, Serialize};
 
+#[derive(Serialize, Deserialize)]
 pub struct Bullet {
     pub cannon_id: i8,
     color: [f32; 4],
@@ -1,16 +1,119 @@
 use graphics::Transformed;
 use opengl_graphics::GlGraphics;
 use rand::Rng;
+use rand_chacha::ChaCha12Rng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+pub mod aim;
+pub mod bot;
+pub mod fire;
+pub mod script;
+
+use aim::{Aim, AimStrategy, ArenaView, Blend, CannonView, Sweep};
+use fire::{FireControl, FireStats, Immediate};
+
+use super::arena::Arena;
+
+/// Held keys of a person driving a cannon, which replace its aiming strategy and fire control.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ManualControl {
+    pub is_turning_ccw: bool,
+    pub is_turning_cw: bool,
+    pub is_firing: bool,
+    // point that the barrel follows while no turn key is held, e.g. the mouse cursor
+    pub target: Option<[f64; 2]>,
+}
+
+/// How strongly the `Blend` strategy follows each of the built-in strategies.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct AimWeights {
+    pub sweep: f64,
+    pub nearest: f64,
+    pub territory: f64,
+}
+
+/// The numbers that decide how a cannon plays, which can be tuned per seat.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct CannonParams {
+    // degrees either side of the direction the cannon faces
+    pub sweep: f64,
+    // degrees per tick
+    pub turn_speed: f64,
+    // ticks between shots
+    pub fire_delay: i32,
+    pub aim_weights: AimWeights,
+}
+
+impl Default for CannonParams {
+    fn default() -> CannonParams {
+        CannonParams {
+            sweep: Cannon::SWEEP,
+            turn_speed: Cannon::SPEED,
+            fire_delay: Cannon::FRAME_DELAY,
+            aim_weights: AimWeights {
+                sweep: 1.0,
+                nearest: 0.0,
+                territory: 0.0,
+            },
+        }
+    }
+}
+
+impl CannonParams {
+    /// The numbers a cannon plays with in `arena` until it is given others.
+    pub fn for_arena(arena: &Arena) -> CannonParams {
+        CannonParams {
+            sweep: arena.sweep,
+            ..CannonParams::default()
+        }
+    }
+
+    pub fn load(path: &str) -> Result<CannonParams, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read parameters '{}': {}", path, e))?;
+        serde_json::from_str(&text).map_err(|e| format!("Invalid parameters in '{}': {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).expect("Parameters are plain numbers");
+        std::fs::write(path, text + "\n")
+            .map_err(|e| format!("Unable to write parameters '{}': {}", path, e))
+    }
+}
+
+/// `AimStrategy::state` of a strategy, or `FireControl::state` of a policy, that remembers
+/// nothing but its own fields.
+fn save_state<T: Serialize>(strategy: &T) -> Value {
+    serde_json::to_value(strategy).expect("Strategies hold plain data")
+}
+
+fn restore_state<T: DeserializeOwned>(strategy: &mut T, state: &Value) {
+    if let Ok(restored) = T::deserialize(state) {
+        *strategy = restored;
+    }
+}
+
+/// A cannon and its state. The aiming strategy and fire control are left out when it is
+/// saved, see `Cannon::controls_state`.
+#[derive(Serialize, Deserialize)]
 pub struct Cannon {
     pub id: i8,
     color: [f32; 4],
     x: f64,
     y: f64,
+    neutral_angle_deg: f64,
     min_angle_deg: f64,
     max_angle_deg: f64,
     current_angle_deg: f64,
-    current_barrel_move: f64,
+    params: CannonParams,
+    #[serde(skip, default = "Cannon::default_aim")]
+    aim: Box<dyn AimStrategy>,
+    #[serde(skip, default = "Cannon::default_fire_control")]
+    fire_control: Box<dyn FireControl>,
+    pub manual: Option<ManualControl>,
+    pub stats: FireStats,
     loaded_shots: i32,
     shot_delay: i32,
     pub is_alive: bool,
@@ -18,25 +121,32 @@ pub struct Cannon {
 
 impl Cannon {
     const SPEED: f64 = 0.3;
-    const SWEEP: f64 = 60.0;
-    const RADIUS: i32 = 20;
+    pub const SWEEP: f64 = 60.0;
+    pub const RADIUS: i32 = 20;
     const FRAME_DELAY: i32 = 4;
+    const MANUAL_SPEED: f64 = 0.6;
 
-    pub fn new(id: i8, hex: &str, is_left: bool, is_top: bool) -> Cannon {
-        let mut h = 2 * super::common::BORDER_SIZE + super::common::SIDE_WIDTH;
-        if is_left {
-            h += Cannon::RADIUS * 3 / 2;
+    pub fn new(
+        id: i8,
+        hex: &str,
+        is_left: bool,
+        is_top: bool,
+        arena: &Arena,
+        rng: &mut ChaCha12Rng,
+    ) -> Cannon {
+        let left = (2 * super::common::BORDER_SIZE + super::common::SIDE_WIDTH) as f64;
+        let top = super::common::BORDER_SIZE as f64;
+        let size = (super::common::CELL_WIDTH * super::common::CELL_EDGES * 2) as f64;
+        let h = if is_left {
+            left + arena.cannon_inset
         } else {
-            h += (super::common::CELL_WIDTH * super::common::CELL_EDGES * 2)
-                - (Cannon::RADIUS * 3 / 2);
-        }
-        let mut v = super::common::BORDER_SIZE;
-        if is_top {
-            v += Cannon::RADIUS * 3 / 2;
+            left + size - arena.cannon_inset
+        };
+        let v = if is_top {
+            top + arena.cannon_inset
         } else {
-            v += (super::common::CELL_WIDTH * super::common::CELL_EDGES * 2)
-                - (Cannon::RADIUS * 3 / 2);
-        }
+            top + size - arena.cannon_inset
+        };
         let neutral: f64;
         if is_left {
             if is_top {
@@ -51,35 +161,142 @@ impl Cannon {
                 neutral = 225.0;
             }
         }
-        let min = neutral - Cannon::SWEEP;
-        let max = neutral + Cannon::SWEEP;
+        let min = neutral - arena.sweep;
+        let max = neutral + arena.sweep;
 
-        let mut rng = rand::thread_rng();
         Cannon {
             id: id,
             color: graphics::color::hex(hex),
-            x: h as f64,
-            y: v as f64,
+            x: h,
+            y: v,
+            neutral_angle_deg: neutral,
             min_angle_deg: min,
             max_angle_deg: max,
             current_angle_deg: rng.gen_range(min..max),
-            current_barrel_move: Cannon::SPEED,
+            params: CannonParams::for_arena(arena),
+            aim: Cannon::default_aim(),
+            fire_control: Cannon::default_fire_control(),
+            manual: None,
+            stats: FireStats::default(),
             loaded_shots: 0,
             shot_delay: Cannon::FRAME_DELAY,
             is_alive: true,
         }
     }
 
-    pub fn turn(&mut self) {
+    fn default_aim() -> Box<dyn AimStrategy> {
+        Box::new(Sweep::new())
+    }
+
+    fn default_fire_control() -> Box<dyn FireControl> {
+        Box::new(Immediate)
+    }
+
+    /// What the aiming strategy and fire control remember between ticks.
+    pub fn controls_state(&self) -> [Value; 2] {
+        [self.aim.state(), self.fire_control.state()]
+    }
+
+    /// Picks up from `controls_state` of a cannon with the same kind of controls.
+    pub fn restore_controls(&mut self, [aim, fire_control]: &[Value; 2]) {
+        self.aim.restore(aim);
+        self.fire_control.restore(fire_control);
+    }
+
+    pub fn set_aim(&mut self, aim: Box<dyn AimStrategy>) {
+        self.aim = aim;
+    }
+
+    /// Plays with tuned numbers instead of the defaults, aiming with `Blend`.
+    pub fn set_params(&mut self, params: CannonParams) {
+        self.params = params;
+        self.min_angle_deg = self.neutral_angle_deg - params.sweep;
+        self.max_angle_deg = self.neutral_angle_deg + params.sweep;
+        self.current_angle_deg = self
+            .current_angle_deg
+            .clamp(self.min_angle_deg, self.max_angle_deg);
+        self.aim = Box::new(Blend::new(params.aim_weights));
+    }
+
+    pub fn aim_name(&self) -> &'static str {
+        if self.manual.is_some() {
+            "human"
+        } else {
+            self.aim.name()
+        }
+    }
+
+    pub fn set_fire_control(&mut self, fire_control: Box<dyn FireControl>) {
+        self.fire_control = fire_control;
+    }
+
+    pub fn fire_control_name(&self) -> &'static str {
+        if self.manual.is_some() {
+            "human"
+        } else {
+            self.fire_control.name()
+        }
+    }
+
+    pub fn view(&self) -> CannonView {
+        CannonView {
+            id: self.id,
+            position: [self.x, self.y],
+            angle_deg: self.current_angle_deg,
+            min_angle_deg: self.min_angle_deg,
+            max_angle_deg: self.max_angle_deg,
+            loaded_shots: self.loaded_shots,
+            is_alive: self.is_alive,
+        }
+    }
+
+    pub fn turn(&mut self, arena: &ArenaView) {
         if self.is_alive {
-            self.current_angle_deg += self.current_barrel_move;
-            if self.current_angle_deg >= self.max_angle_deg {
-                self.current_angle_deg = self.max_angle_deg;
-                self.current_barrel_move = -Cannon::SPEED;
-            } else if self.current_angle_deg <= self.min_angle_deg {
-                self.current_angle_deg = self.min_angle_deg;
-                self.current_barrel_move = Cannon::SPEED;
+            let aim = self.next_aim(arena);
+            // strategies turn at their own pace relative to the tuned speed
+            let turn_rate = match self.manual {
+                Some(_) => aim.turn_rate,
+                None => aim.turn_rate * self.params.turn_speed / Cannon::SPEED,
+            };
+            let delta =
+                (aim.target_angle_deg - self.current_angle_deg).clamp(-turn_rate, turn_rate);
+            self.current_angle_deg =
+                (self.current_angle_deg + delta).clamp(self.min_angle_deg, self.max_angle_deg);
+        }
+    }
+
+    /// Points the barrel exactly where a recording says it was.
+    pub fn turn_to(&mut self, angle_deg: f64) {
+        self.current_angle_deg = angle_deg;
+    }
+
+    fn next_aim(&mut self, arena: &ArenaView) -> Aim {
+        match self.manual {
+            Some(manual) => {
+                let mut direction = 0.0;
+                if manual.is_turning_cw {
+                    direction += 1.0;
+                }
+                if manual.is_turning_ccw {
+                    direction -= 1.0;
+                }
+                let target_angle_deg = match manual.target {
+                    Some(point) if direction == 0.0 => self.view().angle_to(point),
+                    _ => self.current_angle_deg + direction * Cannon::MANUAL_SPEED,
+                };
+                Aim {
+                    target_angle_deg,
+                    turn_rate: Cannon::MANUAL_SPEED,
+                }
             }
+            None => self.aim.aim(&self.view(), arena),
+        }
+    }
+
+    fn wants_to_fire(&mut self, arena: &ArenaView) -> bool {
+        match self.manual {
+            Some(manual) => manual.is_firing,
+            None => self.fire_control.should_fire(&self.view(), arena),
         }
     }
 
@@ -107,19 +324,53 @@ impl Cannon {
         }
     }
 
+    pub fn position(&self) -> [f64; 2] {
+        [self.x, self.y]
+    }
+
+    pub fn radius() -> f64 {
+        Cannon::RADIUS as f64
+    }
+
+    /// Distance from the center at which a bullet destroys this cannon.
+    pub fn hit_radius() -> f64 {
+        Cannon::radius() + super::bullet::Bullet::RADIUS
+    }
+
+    pub fn loaded_shots(&self) -> i32 {
+        self.loaded_shots
+    }
+
     pub fn load(&mut self, num_shots: i32) {
         if self.is_alive {
-            self.loaded_shots += num_shots;
+            self.loaded_shots = self.loaded_shots.saturating_add(num_shots);
         }
     }
 
-    pub fn shoot(&mut self) -> Option<super::bullet::Bullet> {
+    pub fn shoot(&mut self, arena: &ArenaView) -> Option<super::bullet::Bullet> {
+        if self.is_alive && self.manual.is_none() {
+            self.aim.prepare(&self.view(), arena);
+        }
+        self.release(|cannon| cannon.wants_to_fire(arena))
+    }
+
+    /// Fires when a recording says it did, rather than when its controls want to.
+    pub fn shoot_as_recorded(&mut self, fired: bool) -> Option<super::bullet::Bullet> {
+        self.release(|_| fired)
+    }
+
+    /// Fires the next loaded shot once the delay since the last one has passed, if asked to.
+    fn release<F>(&mut self, wants_to_fire: F) -> Option<super::bullet::Bullet>
+    where
+        F: FnOnce(&mut Cannon) -> bool,
+    {
         if self.is_alive && self.loaded_shots > 0 {
             if self.shot_delay > 0 {
                 self.shot_delay -= 1;
-            } else {
-                self.shot_delay = Cannon::FRAME_DELAY;
+            } else if wants_to_fire(self) {
+                self.shot_delay = self.params.fire_delay;
                 self.loaded_shots -= 1;
+                self.stats.shots_fired += 1;
                 return Some(super::bullet::Bullet::new(
                     self.id,
                     self.color,
@@ -137,7 +388,7 @@ impl Cannon {
             let a = (self.x - bullet.position[0]).abs();
             let b = (self.y - bullet.position[1]).abs();
             let distance = a.hypot(b);
-            if distance < (Cannon::RADIUS as f64 + super::bullet::Bullet::RADIUS) {
+            if distance < Cannon::hit_radius() {
                 self.is_alive = false;
                 bullet.is_alive = false;
             }
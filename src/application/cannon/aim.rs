@@ -0,0 +1,347 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::super::bullet::Bullet;
+use super::super::common;
+use super::super::grid::Grid;
+use super::{restore_state, save_state, AimWeights, Cannon};
+
+/// Read-only snapshot of a cannon, as seen by aiming strategies.
+#[derive(Clone, Copy)]
+pub struct CannonView {
+    pub id: i8,
+    pub position: [f64; 2],
+    pub angle_deg: f64,
+    pub min_angle_deg: f64,
+    pub max_angle_deg: f64,
+    pub loaded_shots: i32,
+    pub is_alive: bool,
+}
+
+impl CannonView {
+    /// Angle from this cannon to `point`, expressed in the same range as its sweep limits.
+    pub fn angle_to(&self, point: [f64; 2]) -> f64 {
+        let angle = (point[1] - self.position[1])
+            .atan2(point[0] - self.position[0])
+            .to_degrees();
+        let center = (self.min_angle_deg + self.max_angle_deg) / 2.0;
+        angle + ((center - angle) / 360.0).round() * 360.0
+    }
+}
+
+/// Read-only view of the arena that strategies base their decisions on.
+pub struct ArenaView<'a> {
+    pub grid: &'a Grid,
+    pub cannons: &'a [CannonView],
+    pub bullets: &'a [Bullet],
+    pub field_rect: [f64; 4],
+}
+
+impl<'a> ArenaView<'a> {
+    pub fn enemies(&self, id: i8) -> impl Iterator<Item = &CannonView> {
+        self.cannons
+            .iter()
+            .filter(move |c| c.is_alive && c.id != id)
+    }
+
+    /// Counts the cells owned by enemies of `id` on a straight line from `position` to the
+    /// edge of the field, returning (enemy cells, all cells).
+    pub fn cells_along(&self, id: i8, position: [f64; 2], angle_deg: f64) -> (usize, usize) {
+        let step = common::CELL_WIDTH as f64;
+        let (dx, dy) = (
+            angle_deg.to_radians().cos() * step,
+            angle_deg.to_radians().sin() * step,
+        );
+        let [left, top, width, height] = self.field_rect;
+        let (mut x, mut y) = (position[0] + dx, position[1] + dy);
+        let (mut enemy, mut total) = (0, 0);
+        while x >= left && y >= top && x < left + width && y < top + height {
+            let owner = self.grid.cells[common::calc_physical_index(x - left, y - top)];
+            if owner != id {
+                enemy += 1;
+            }
+            total += 1;
+            x += dx;
+            y += dy;
+        }
+        (enemy, total)
+    }
+}
+
+/// Where the barrel should point and how many degrees it may turn this tick.
+pub struct Aim {
+    pub target_angle_deg: f64,
+    pub turn_rate: f64,
+}
+
+pub trait AimStrategy: Send {
+    fn name(&self) -> &'static str;
+    fn aim(&mut self, me: &CannonView, arena: &ArenaView) -> Aim;
+
+    /// Called every tick before the cannon may fire, and so before `aim`, for a strategy that
+    /// also decides when to fire to do so at the angle the barrel has now.
+    fn prepare(&mut self, _me: &CannonView, _arena: &ArenaView) {}
+
+    /// What the strategy remembers between ticks, for a snapshot of the match.
+    fn state(&self) -> Value {
+        Value::Null
+    }
+
+    /// Picks up from what `state` returned for a strategy of the same kind.
+    fn restore(&mut self, _state: &Value) {}
+}
+
+pub const STRATEGY_NAMES: [&str; 5] = ["sweep", "nearest", "threat", "territory", "jitter"];
+
+/// Creates a strategy, where `seed` drives any randomness so that matches can be replayed.
+pub fn strategy_by_name(name: &str, seed: u64) -> Option<Box<dyn AimStrategy>> {
+    match name {
+        "sweep" => Some(Box::new(Sweep::new())),
+        "nearest" => Some(Box::new(NearestEnemy)),
+        "threat" => Some(Box::new(ReturnFire)),
+        "territory" => Some(Box::new(LargestTerritory)),
+        "jitter" => Some(Box::new(RandomJitter::new(seed))),
+        _ => None,
+    }
+}
+
+/// Swings the barrel back and forth between the sweep limits.
+#[derive(Serialize, Deserialize)]
+pub struct Sweep {
+    direction: f64,
+}
+
+impl Sweep {
+    pub fn new() -> Sweep {
+        Sweep { direction: 1.0 }
+    }
+}
+
+impl AimStrategy for Sweep {
+    fn name(&self) -> &'static str {
+        "sweep"
+    }
+
+    fn state(&self) -> Value {
+        save_state(self)
+    }
+
+    fn restore(&mut self, state: &Value) {
+        restore_state(self, state);
+    }
+
+    fn aim(&mut self, me: &CannonView, _arena: &ArenaView) -> Aim {
+        if me.angle_deg >= me.max_angle_deg {
+            self.direction = -1.0;
+        } else if me.angle_deg <= me.min_angle_deg {
+            self.direction = 1.0;
+        }
+        Aim {
+            target_angle_deg: if self.direction > 0.0 {
+                me.max_angle_deg
+            } else {
+                me.min_angle_deg
+            },
+            turn_rate: Cannon::SPEED,
+        }
+    }
+}
+
+/// Tracks the closest enemy cannon that is still alive.
+pub struct NearestEnemy;
+
+impl AimStrategy for NearestEnemy {
+    fn name(&self) -> &'static str {
+        "nearest"
+    }
+
+    fn aim(&mut self, me: &CannonView, arena: &ArenaView) -> Aim {
+        let distance =
+            |c: &CannonView| (c.position[0] - me.position[0]).hypot(c.position[1] - me.position[1]);
+        let target = arena
+            .enemies(me.id)
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)));
+        Aim {
+            target_angle_deg: target.map_or(me.angle_deg, |c| me.angle_to(c.position)),
+            turn_rate: Cannon::SPEED,
+        }
+    }
+}
+
+/// Turns on whichever enemy fired the bullet closest to this cannon, or the nearest enemy
+/// while none of theirs is in flight.
+pub struct ReturnFire;
+
+impl AimStrategy for ReturnFire {
+    fn name(&self) -> &'static str {
+        "threat"
+    }
+
+    fn aim(&mut self, me: &CannonView, arena: &ArenaView) -> Aim {
+        let distance = |p: [f64; 2]| (p[0] - me.position[0]).hypot(p[1] - me.position[1]);
+        let shooter = arena
+            .bullets
+            .iter()
+            .filter(|b| b.is_alive && b.cannon_id != me.id)
+            .min_by(|a, b| distance(a.position).total_cmp(&distance(b.position)))
+            .and_then(|b| arena.enemies(me.id).find(|c| c.id == b.cannon_id));
+        match shooter {
+            Some(c) => Aim {
+                target_angle_deg: me.angle_to(c.position),
+                turn_rate: Cannon::SPEED,
+            },
+            None => NearestEnemy.aim(me, arena),
+        }
+    }
+}
+
+/// Aims at the middle of whichever enemy currently holds the most cells.
+pub struct LargestTerritory;
+
+impl AimStrategy for LargestTerritory {
+    fn name(&self) -> &'static str {
+        "territory"
+    }
+
+    fn aim(&mut self, me: &CannonView, arena: &ArenaView) -> Aim {
+        let target = arena
+            .enemies(me.id)
+            .max_by_key(|c| arena.grid.territory(c.id))
+            .map(|c| c.id);
+        let mut sum = [0.0, 0.0];
+        let mut count = 0;
+        if let Some(owner) = target {
+            for x in 0..(common::CELL_EDGES * 2) {
+                for y in 0..(common::CELL_EDGES * 2) {
+                    if arena.grid.cells[common::calc_logical_index(x, y)] == owner {
+                        sum[0] += x as f64;
+                        sum[1] += y as f64;
+                        count += 1;
+                    }
+                }
+            }
+        }
+        let target_angle_deg = if count > 0 {
+            let cell = common::CELL_WIDTH as f64;
+            me.angle_to([
+                arena.field_rect[0] + (sum[0] / count as f64 + 0.5) * cell,
+                arena.field_rect[1] + (sum[1] / count as f64 + 0.5) * cell,
+            ])
+        } else {
+            me.angle_deg
+        };
+        Aim {
+            target_angle_deg,
+            turn_rate: Cannon::SPEED,
+        }
+    }
+}
+
+/// Points between where `Sweep`, `NearestEnemy` and `LargestTerritory` would, each counted
+/// by its weight.
+#[derive(Serialize, Deserialize)]
+pub struct Blend {
+    weights: AimWeights,
+    sweep: Sweep,
+}
+
+impl Blend {
+    pub fn new(weights: AimWeights) -> Blend {
+        Blend {
+            weights,
+            sweep: Sweep::new(),
+        }
+    }
+}
+
+impl AimStrategy for Blend {
+    fn name(&self) -> &'static str {
+        "blend"
+    }
+
+    fn state(&self) -> Value {
+        save_state(self)
+    }
+
+    fn restore(&mut self, state: &Value) {
+        restore_state(self, state);
+    }
+
+    fn aim(&mut self, me: &CannonView, arena: &ArenaView) -> Aim {
+        let AimWeights {
+            sweep,
+            nearest,
+            territory,
+        } = self.weights;
+        let mut parts = vec![(sweep, self.sweep.aim(me, arena))];
+        // the others are costly enough to skip when they don't count
+        if nearest > 0.0 {
+            parts.push((nearest, NearestEnemy.aim(me, arena)));
+        }
+        if territory > 0.0 {
+            parts.push((territory, LargestTerritory.aim(me, arena)));
+        }
+        let total = parts.iter().map(|(weight, _)| weight).sum::<f64>();
+        let target_angle_deg = if total > 0.0 {
+            parts
+                .iter()
+                .map(|(weight, aim)| weight * aim.target_angle_deg)
+                .sum::<f64>()
+                / total
+        } else {
+            me.angle_deg
+        };
+        Aim {
+            target_angle_deg,
+            turn_rate: Cannon::SPEED,
+        }
+    }
+}
+
+/// Picks a new random direction within the sweep limits every so often.
+#[derive(Serialize, Deserialize)]
+pub struct RandomJitter {
+    target_angle_deg: Option<f64>,
+    ticks_left: i32,
+    rng: ChaCha12Rng,
+}
+
+impl RandomJitter {
+    const TICKS_PER_TARGET: i32 = 90;
+
+    pub fn new(seed: u64) -> RandomJitter {
+        RandomJitter {
+            target_angle_deg: None,
+            ticks_left: 0,
+            rng: ChaCha12Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl AimStrategy for RandomJitter {
+    fn name(&self) -> &'static str {
+        "jitter"
+    }
+
+    fn state(&self) -> Value {
+        save_state(self)
+    }
+
+    fn restore(&mut self, state: &Value) {
+        restore_state(self, state);
+    }
+
+    fn aim(&mut self, me: &CannonView, _arena: &ArenaView) -> Aim {
+        self.ticks_left -= 1;
+        if self.ticks_left <= 0 || self.target_angle_deg.is_none() {
+            self.ticks_left = RandomJitter::TICKS_PER_TARGET;
+            self.target_angle_deg = Some(self.rng.gen_range(me.min_angle_deg..me.max_angle_deg));
+        }
+        Aim {
+            target_angle_deg: self.target_angle_deg.unwrap_or(me.angle_deg),
+            turn_rate: Cannon::SPEED * 2.0,
+        }
+    }
+}
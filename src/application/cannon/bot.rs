@@ -0,0 +1,266 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::super::common;
+use super::aim::{Aim, AimStrategy, ArenaView, CannonView, Sweep};
+use super::fire::FireControl;
+use super::Cannon;
+
+#[derive(Serialize)]
+struct EnemyObservation {
+    id: i8,
+    position: [f64; 2],
+    angle: f64,
+}
+
+#[derive(Serialize)]
+struct GridObservation {
+    size: usize,
+    cells: Vec<Vec<i8>>,
+}
+
+/// Sent to the bot every tick, one JSON object per line.
+#[derive(Serialize)]
+struct Observation {
+    tick: u64,
+    id: i8,
+    position: [f64; 2],
+    angle: f64,
+    min_angle: f64,
+    max_angle: f64,
+    loaded_shots: i32,
+    enemies: Vec<EnemyObservation>,
+    // the grid covers this rectangle, [left, top, width, height]
+    field: [f64; 4],
+    grid: GridObservation,
+}
+
+/// The bot's answer, e.g. `{"angle": 45.0, "fire": true}`. A reply naming an older tick is
+/// ignored.
+#[derive(Deserialize)]
+struct Reply {
+    angle: f64,
+    #[serde(default)]
+    fire: bool,
+    tick: Option<u64>,
+}
+
+/// The aiming strategy and fire control that together drive a cannon.
+pub type Controls = (Box<dyn AimStrategy>, Box<dyn FireControl>);
+
+/// Creates the aiming strategy and fire control of a cannon driven by an external program.
+///
+/// Every tick the program gets an observation of the arena on its standard input and answers
+/// with a line on its standard output. The barrel turns towards the angle it answers at the
+/// speed of the built-in strategies, and the cannon fires while it answers `"fire": true`. A
+/// shot leaves before the barrel turns, at the angle in the observation it answered.
+/// When an answer takes longer than `TIMEOUT`, or the program has exited, the cannon sweeps
+/// for that tick instead, and after `GIVE_UP_TICKS` such ticks in a row it sweeps for good.
+pub fn spawn(command: &str) -> Result<Controls, String> {
+    let mut words = command.split_whitespace();
+    let program = words.next().ok_or("The bot command is empty")?;
+    let mut child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Unable to start bot '{}': {}", command, e))?;
+    let mut input = child.stdin.take().ok_or("Unable to write to the bot")?;
+    let output = child.stdout.take().ok_or("Unable to read from the bot")?;
+
+    // a bot that stops reading blocks this thread rather than the game
+    let (lines, queue) = mpsc::sync_channel::<String>(BotAim::QUEUED_LINES);
+    thread::spawn(move || {
+        for line in queue {
+            if input.write_all(line.as_bytes()).is_err() || input.flush().is_err() {
+                return;
+            }
+        }
+    });
+
+    let (sender, replies) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(output).lines().map_while(Result::ok) {
+            // answers that can't be read count as missing
+            if let Ok(reply) = serde_json::from_str::<Reply>(&line) {
+                if sender.send(reply).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    let fire = Arc::new(AtomicBool::new(false));
+    let aim = BotAim {
+        command: command.to_string(),
+        child,
+        input: Some(lines),
+        replies,
+        fire: fire.clone(),
+        reply: None,
+        fallback: Sweep::new(),
+        tick: 0,
+        missed: 0,
+    };
+    Ok((Box::new(aim), Box::new(BotFire { fire })))
+}
+
+/// Talks to the bot process, which lives as long as this strategy.
+struct BotAim {
+    command: String,
+    child: Child,
+    // lines for the writer thread, none once the bot has been given up on
+    input: Option<SyncSender<String>>,
+    replies: Receiver<Reply>,
+    // the latest fire decision, shared with `BotFire`
+    fire: Arc<AtomicBool>,
+    // the answer for this tick, from `prepare` until `aim` turns the barrel
+    reply: Option<Reply>,
+    fallback: Sweep,
+    tick: u64,
+    // ticks in a row without an answer in time
+    missed: u32,
+}
+
+impl BotAim {
+    const TIMEOUT: Duration = Duration::from_millis(10);
+    // observations waiting to be written, beyond which the bot is taken to have stopped reading
+    const QUEUED_LINES: usize = 4;
+    // a second of play
+    const GIVE_UP_TICKS: u32 = 120;
+    // the grid is sent as blocks of this many cells square, each owned by its most common owner
+    const GRID_BLOCK: i32 = 4;
+
+    fn observe(&self, me: &CannonView, arena: &ArenaView) -> Observation {
+        Observation {
+            tick: self.tick,
+            id: me.id,
+            position: me.position,
+            angle: me.angle_deg,
+            min_angle: me.min_angle_deg,
+            max_angle: me.max_angle_deg,
+            loaded_shots: me.loaded_shots,
+            enemies: arena
+                .enemies(me.id)
+                .map(|c| EnemyObservation {
+                    id: c.id,
+                    position: c.position,
+                    angle: c.angle_deg,
+                })
+                .collect(),
+            field: arena.field_rect,
+            grid: BotAim::downsample(arena),
+        }
+    }
+
+    fn downsample(arena: &ArenaView) -> GridObservation {
+        let size = common::CELL_EDGES * 2 / BotAim::GRID_BLOCK;
+        let block_owner = |bx: i32, by: i32| {
+            let mut counts = [0; 5];
+            for x in bx * BotAim::GRID_BLOCK..(bx + 1) * BotAim::GRID_BLOCK {
+                for y in by * BotAim::GRID_BLOCK..(by + 1) * BotAim::GRID_BLOCK {
+                    let owner = arena.grid.cells[common::calc_logical_index(x, y)];
+                    counts[owner as usize] += 1;
+                }
+            }
+            // the first of the most common owners
+            (0..counts.len())
+                .rev()
+                .max_by_key(|i| counts[*i])
+                .unwrap_or(0) as i8
+        };
+        GridObservation {
+            size: size as usize,
+            cells: (0..size)
+                .map(|y| (0..size).map(|x| block_owner(x, y)).collect())
+                .collect(),
+        }
+    }
+
+    /// Sends an observation and waits for the answer to it.
+    fn exchange(&mut self, observation: &Observation) -> Option<Reply> {
+        // answers that arrived too late for earlier ticks are of no use now
+        while self.replies.try_recv().is_ok() {}
+        let mut line = serde_json::to_string(observation).ok()?;
+        line.push('\n');
+        // a full queue is as good as a missed answer, without waiting for it
+        self.input.as_ref()?.try_send(line).ok()?;
+        loop {
+            let reply = self.replies.recv_timeout(BotAim::TIMEOUT).ok()?;
+            if reply.tick.is_none_or(|tick| tick == self.tick) {
+                return Some(reply);
+            }
+        }
+    }
+}
+
+impl AimStrategy for BotAim {
+    fn name(&self) -> &'static str {
+        "bot"
+    }
+
+    // asks the bot before the cannon fires, so that it fires at the angle the bot saw
+    fn prepare(&mut self, me: &CannonView, arena: &ArenaView) {
+        self.tick += 1;
+        let observation = self.observe(me, arena);
+        self.reply = self.exchange(&observation);
+        match &self.reply {
+            Some(reply) => {
+                self.missed = 0;
+                self.fire.store(reply.fire, Ordering::Relaxed);
+            }
+            None => {
+                self.missed += 1;
+                if self.missed == BotAim::GIVE_UP_TICKS {
+                    eprintln!(
+                        "Bot '{}' hasn't answered for a second, player {} sweeps from now on",
+                        self.command, me.id
+                    );
+                    // closes the queue, so the writer thread ends too
+                    self.input = None;
+                }
+                self.fire.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn aim(&mut self, me: &CannonView, arena: &ArenaView) -> Aim {
+        match self.reply.take() {
+            Some(reply) => Aim {
+                target_angle_deg: reply.angle,
+                turn_rate: Cannon::SPEED,
+            },
+            None => self.fallback.aim(me, arena),
+        }
+    }
+}
+
+impl Drop for BotAim {
+    fn drop(&mut self) {
+        // the process may well have exited already
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Fires when the bot last said so.
+struct BotFire {
+    fire: Arc<AtomicBool>,
+}
+
+impl FireControl for BotFire {
+    fn name(&self) -> &'static str {
+        "bot"
+    }
+
+    fn should_fire(&mut self, _me: &CannonView, _arena: &ArenaView) -> bool {
+        self.fire.load(Ordering::Relaxed)
+    }
+}
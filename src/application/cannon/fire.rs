@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::aim::{ArenaView, CannonView};
+use super::{restore_state, save_state};
+
+/// Decides whether a cannon releases its next loaded shot once the barrel is ready to fire.
+pub trait FireControl: Send {
+    fn name(&self) -> &'static str;
+    fn should_fire(&mut self, me: &CannonView, arena: &ArenaView) -> bool;
+
+    /// What the policy remembers between ticks, for a snapshot of the match.
+    fn state(&self) -> Value {
+        Value::Null
+    }
+
+    /// Picks up from what `state` returned for a policy of the same kind.
+    fn restore(&mut self, _state: &Value) {}
+}
+
+pub const POLICY_NAMES: [&str; 3] = ["immediate", "burst", "targeted"];
+
+pub fn policy_by_name(name: &str) -> Option<Box<dyn FireControl>> {
+    match name {
+        "immediate" => Some(Box::new(Immediate)),
+        "burst" => Some(Box::new(Burst::new())),
+        "targeted" => Some(Box::new(Targeted::new())),
+        _ => None,
+    }
+}
+
+/// How well a cannon turned its shots into captured cells.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct FireStats {
+    pub shots_fired: u32,
+    pub cells_captured: u32,
+}
+
+impl FireStats {
+    pub fn cells_per_shot(&self) -> f64 {
+        if self.shots_fired == 0 {
+            0.0
+        } else {
+            self.cells_captured as f64 / self.shots_fired as f64
+        }
+    }
+}
+
+/// Fires every loaded shot as soon as possible.
+pub struct Immediate;
+
+impl FireControl for Immediate {
+    fn name(&self) -> &'static str {
+        "immediate"
+    }
+
+    fn should_fire(&mut self, _me: &CannonView, _arena: &ArenaView) -> bool {
+        true
+    }
+}
+
+/// Holds shots until enough have been collected, then empties the cannon in one burst.
+#[derive(Serialize, Deserialize)]
+pub struct Burst {
+    is_releasing: bool,
+}
+
+impl Burst {
+    const MIN_SHOTS: i32 = 32;
+
+    pub fn new() -> Burst {
+        Burst {
+            is_releasing: false,
+        }
+    }
+}
+
+impl FireControl for Burst {
+    fn name(&self) -> &'static str {
+        "burst"
+    }
+
+    fn state(&self) -> Value {
+        save_state(self)
+    }
+
+    fn restore(&mut self, state: &Value) {
+        restore_state(self, state);
+    }
+
+    fn should_fire(&mut self, me: &CannonView, _arena: &ArenaView) -> bool {
+        if me.loaded_shots >= Burst::MIN_SHOTS {
+            self.is_releasing = true;
+        }
+        let fire = self.is_releasing;
+        if me.loaded_shots <= 1 {
+            // this is the last shot of the burst
+            self.is_releasing = false;
+        }
+        fire
+    }
+}
+
+/// Holds shots while the barrel points mostly at its own cells, and releases them at enemy land.
+#[derive(Serialize, Deserialize)]
+pub struct Targeted {
+    is_releasing: bool,
+}
+
+impl Targeted {
+    // share of enemy cells along the line of fire that makes a direction worth shooting at
+    const MIN_ENEMY_SHARE: f64 = 0.3;
+    // never hold more than this many shots, whatever the barrel points at
+    const MAX_HELD_SHOTS: i32 = 64;
+
+    pub fn new() -> Targeted {
+        Targeted {
+            is_releasing: false,
+        }
+    }
+}
+
+impl FireControl for Targeted {
+    fn name(&self) -> &'static str {
+        "targeted"
+    }
+
+    fn state(&self) -> Value {
+        save_state(self)
+    }
+
+    fn restore(&mut self, state: &Value) {
+        restore_state(self, state);
+    }
+
+    fn should_fire(&mut self, me: &CannonView, arena: &ArenaView) -> bool {
+        let (enemy, total) = arena.cells_along(me.id, me.position, me.angle_deg);
+        let is_valuable = total > 0 && enemy as f64 / total as f64 >= Targeted::MIN_ENEMY_SHARE;
+        if me.loaded_shots >= Targeted::MAX_HELD_SHOTS {
+            self.is_releasing = true;
+        }
+        let fire = is_valuable || self.is_releasing;
+        if me.loaded_shots <= 1 {
+            self.is_releasing = false;
+        }
+        fire
+    }
+}
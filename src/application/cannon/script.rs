@@ -0,0 +1,252 @@
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+use serde_json::Value;
+
+use super::super::common;
+use super::aim::{Aim, AimStrategy, ArenaView, CannonView, Sweep};
+use super::bot::Controls;
+use super::fire::FireControl;
+use super::Cannon;
+
+/// The grid as a script sees it, through `grid.owner_at(x, y)` and `grid.cell_size`.
+#[derive(Clone)]
+struct GridView {
+    cells: Arc<Vec<i8>>,
+    field_rect: [f64; 4],
+}
+
+impl GridView {
+    /// Owner of the cell under a point of the field, 0 for neutral and -1 off the field.
+    fn owner_at(&mut self, x: f64, y: f64) -> i64 {
+        let [left, top, width, height] = self.field_rect;
+        if x < left || y < top || x >= left + width || y >= top + height {
+            return -1;
+        }
+        self.cells[common::calc_physical_index(x - left, y - top)] as i64
+    }
+}
+
+/// Creates the aiming strategy and fire control of a cannon driven by a Rhai script.
+///
+/// The script defines `fn aim(me, enemies, bullets, grid)`, which is called every tick and
+/// answers with a map like `#{angle: 90.0, fire: true}`. The barrel turns towards the angle
+/// at the speed of the built-in strategies, after the cannon has fired if the script said so,
+/// at the angle `me` had. A call may take at most `MAX_OPERATIONS` steps;
+/// when it fails or runs out, the cannon sweeps for that tick instead. Changes to the file are
+/// picked up while the match runs.
+pub fn load(path: &str) -> Result<Controls, String> {
+    let engine = ScriptAim::engine();
+    let source =
+        fs::read_to_string(path).map_err(|e| format!("Unable to read script '{}': {}", path, e))?;
+    let ast = engine
+        .compile(source)
+        .map_err(|e| format!("Unable to load script '{}': {}", path, e))?;
+
+    let fire = Arc::new(AtomicBool::new(false));
+    let aim = ScriptAim {
+        engine,
+        ast,
+        path: path.to_string(),
+        modified: ScriptAim::modified(path),
+        ticks_to_reload: ScriptAim::RELOAD_TICKS,
+        fire: fire.clone(),
+        angle: None,
+        fallback: Sweep::new(),
+        last_error: None,
+    };
+    Ok((Box::new(aim), Box::new(ScriptFire { fire })))
+}
+
+struct ScriptAim {
+    engine: Engine,
+    ast: AST,
+    path: String,
+    modified: Option<SystemTime>,
+    ticks_to_reload: u32,
+    // the latest fire decision, shared with `ScriptFire`
+    fire: Arc<AtomicBool>,
+    // the angle answered this tick, from `prepare` until `aim` turns the barrel
+    angle: Option<f64>,
+    fallback: Sweep,
+    // reported once until the script works again, rather than every tick
+    last_error: Option<String>,
+}
+
+impl ScriptAim {
+    const MAX_OPERATIONS: u64 = 10_000;
+    const MAX_SIZE: usize = 10_000;
+    // how often the file is checked for changes, half a second of play
+    const RELOAD_TICKS: u32 = 60;
+
+    /// An engine that can only compute, with no way to reach files, processes or the network,
+    /// and limits on how much a script may do.
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(ScriptAim::MAX_OPERATIONS)
+            .set_max_string_size(ScriptAim::MAX_SIZE)
+            .set_max_array_size(ScriptAim::MAX_SIZE)
+            .set_max_map_size(ScriptAim::MAX_SIZE)
+            .on_print(|text| eprintln!("{}", text))
+            .on_debug(|text, _, _| eprintln!("{}", text));
+        engine
+            .register_type_with_name::<GridView>("Grid")
+            .register_fn("owner_at", GridView::owner_at)
+            .register_get("cell_size", |_: &mut GridView| common::CELL_WIDTH as f64);
+        engine
+    }
+
+    fn modified(path: &str) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Compiles the file again when it has changed, keeping the old script if the new one
+    /// has errors.
+    fn reload_if_changed(&mut self) {
+        self.ticks_to_reload -= 1;
+        if self.ticks_to_reload > 0 {
+            return;
+        }
+        self.ticks_to_reload = ScriptAim::RELOAD_TICKS;
+        let modified = ScriptAim::modified(&self.path);
+        if modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+        let compiled = fs::read_to_string(&self.path)
+            .map_err(|e| e.to_string())
+            .and_then(|source| self.engine.compile(source).map_err(|e| e.to_string()));
+        match compiled {
+            Ok(ast) => {
+                self.ast = ast;
+                self.last_error = None;
+                eprintln!("Reloaded script '{}'", self.path);
+            }
+            Err(message) => eprintln!("Script '{}' not reloaded: {}", self.path, message),
+        }
+    }
+
+    fn cannon(view: &CannonView) -> Dynamic {
+        let mut map = Map::new();
+        map.insert("id".into(), (view.id as i64).into());
+        map.insert("x".into(), view.position[0].into());
+        map.insert("y".into(), view.position[1].into());
+        map.insert("angle".into(), view.angle_deg.into());
+        map.insert("min_angle".into(), view.min_angle_deg.into());
+        map.insert("max_angle".into(), view.max_angle_deg.into());
+        map.insert("loaded_shots".into(), (view.loaded_shots as i64).into());
+        map.into()
+    }
+
+    /// Calls the script, returning the angle and fire decision it answered.
+    fn run(&mut self, me: &CannonView, arena: &ArenaView) -> Result<(f64, bool), String> {
+        let enemies = arena
+            .enemies(me.id)
+            .map(ScriptAim::cannon)
+            .collect::<Array>();
+        let bullets = arena
+            .bullets
+            .iter()
+            .filter(|b| b.is_alive)
+            .map(|b| {
+                let mut map = Map::new();
+                map.insert("owner".into(), (b.cannon_id as i64).into());
+                map.insert("x".into(), b.position[0].into());
+                map.insert("y".into(), b.position[1].into());
+                Dynamic::from(map)
+            })
+            .collect::<Array>();
+        let grid = GridView {
+            cells: Arc::new(arena.grid.cells.to_vec()),
+            field_rect: arena.field_rect,
+        };
+
+        let answer = self
+            .engine
+            .call_fn::<Dynamic>(
+                &mut Scope::new(),
+                &self.ast,
+                "aim",
+                (ScriptAim::cannon(me), enemies, bullets, grid),
+            )
+            .map_err(|e| e.to_string())?;
+        let expected = || "aim must answer with a map like #{angle: 90.0, fire: true}".to_string();
+        let answer = answer.try_cast::<Map>().ok_or_else(expected)?;
+        let angle = answer.get("angle").ok_or_else(expected)?;
+        let angle = angle
+            .as_float()
+            .or_else(|_| angle.as_int().map(|a| a as f64))
+            .map_err(|_| expected())?;
+        let fire = match answer.get("fire") {
+            Some(fire) => fire.as_bool().map_err(|_| expected())?,
+            None => false,
+        };
+        Ok((angle, fire))
+    }
+}
+
+impl AimStrategy for ScriptAim {
+    fn name(&self) -> &'static str {
+        "script"
+    }
+
+    // the script itself is read from its file again, so only its last answer is kept
+    fn state(&self) -> Value {
+        Value::Bool(self.fire.load(Ordering::Relaxed))
+    }
+
+    fn restore(&mut self, state: &Value) {
+        if let Some(fire) = state.as_bool() {
+            self.fire.store(fire, Ordering::Relaxed);
+        }
+    }
+
+    // runs the script before the cannon fires, so that it fires at the angle the script saw
+    fn prepare(&mut self, me: &CannonView, arena: &ArenaView) {
+        self.reload_if_changed();
+        match self.run(me, arena) {
+            Ok((angle, fire)) => {
+                self.last_error = None;
+                self.fire.store(fire, Ordering::Relaxed);
+                self.angle = Some(angle);
+            }
+            Err(message) => {
+                if self.last_error.as_ref() != Some(&message) {
+                    eprintln!("Script '{}': {}", self.path, message);
+                    self.last_error = Some(message);
+                }
+                self.fire.store(true, Ordering::Relaxed);
+                self.angle = None;
+            }
+        }
+    }
+
+    fn aim(&mut self, me: &CannonView, arena: &ArenaView) -> Aim {
+        match self.angle.take() {
+            Some(angle) => Aim {
+                target_angle_deg: angle,
+                turn_rate: Cannon::SPEED,
+            },
+            None => self.fallback.aim(me, arena),
+        }
+    }
+}
+
+/// Fires when the script last said so.
+struct ScriptFire {
+    fire: Arc<AtomicBool>,
+}
+
+impl FireControl for ScriptFire {
+    fn name(&self) -> &'static str {
+        "script"
+    }
+
+    fn should_fire(&mut self, _me: &CannonView, _arena: &ArenaView) -> bool {
+        self.fire.load(Ordering::Relaxed)
+    }
+}
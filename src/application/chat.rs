@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::Simulation;
+
+/// What the audience can do from chat.
+#[derive(Clone, Copy)]
+enum Command {
+    // `!red`: one extra shot
+    Cheer(i8),
+    // `!boost blue`: a handful of shots, rarely
+    Boost(i8),
+    // `!drop 3`: knock the falling pucks of a plinko board sideways
+    Drop(i8),
+}
+
+impl Command {
+    const NAMES: [&'static str; 4] = ["red", "green", "blue", "yellow"];
+    const CHEER_SHOTS: i32 = 1;
+    const BOOST_SHOTS: i32 = 8;
+    const NUDGE_DEGREES: f64 = 30.0;
+
+    /// Reads a chat line like `!boost blue`, where players are named by color or number.
+    fn parse(text: &str) -> Option<Command> {
+        let mut words = text.split_whitespace().map(str::to_lowercase);
+        let first = words.next()?;
+        let name = first.strip_prefix('!')?;
+        match name {
+            "boost" => Some(Command::Boost(Command::player(&words.next()?)?)),
+            "drop" => Some(Command::Drop(Command::player(&words.next()?)?)),
+            _ => Some(Command::Cheer(Command::player(name)?)),
+        }
+    }
+
+    fn player(word: &str) -> Option<i8> {
+        match Command::NAMES.iter().position(|n| *n == word) {
+            Some(i) => Some((i + 1) as i8),
+            None => word.parse().ok().filter(|id| (1..=4).contains(id)),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Command::Cheer(_) => "!<color>",
+            Command::Boost(_) => "!boost",
+            Command::Drop(_) => "!drop",
+        }
+    }
+
+    /// How long everyone waits before the same kind of command works again.
+    fn cooldown(&self) -> Duration {
+        match self {
+            Command::Cheer(_) => Duration::ZERO,
+            Command::Boost(_) => Duration::from_secs(20),
+            Command::Drop(_) => Duration::from_secs(5),
+        }
+    }
+
+    fn apply(&self, sim: &mut Simulation) {
+        match *self {
+            Command::Cheer(id) => sim.load_cannon(id, Command::CHEER_SHOTS),
+            Command::Boost(id) => sim.load_cannon(id, Command::BOOST_SHOTS),
+            Command::Drop(id) => {
+                let degrees = if rand::random() {
+                    Command::NUDGE_DEGREES
+                } else {
+                    -Command::NUDGE_DEGREES
+                };
+                sim.nudge_plinko(id, degrees);
+            }
+        }
+    }
+}
+
+/// Lets the audience of an IRC channel take part in the match with commands like `!red`,
+/// `!boost blue` and `!drop 3`.
+///
+/// Each person can use one command every `USER_COOLDOWN`, and `!boost` and `!drop` have a
+/// cooldown for the whole channel on top of that, which `status` describes for the HUD.
+pub struct ChatClient {
+    commands: Receiver<(String, Command)>,
+    user_ready: HashMap<String, Instant>,
+    command_ready: HashMap<&'static str, Instant>,
+    is_connected: bool,
+}
+
+impl ChatClient {
+    const USER_COOLDOWN: Duration = Duration::from_secs(10);
+
+    /// Connects to `server` (`host:port`) and joins `channel` as `nick`.
+    pub fn connect(server: &str, channel: &str, nick: &str) -> Result<ChatClient, String> {
+        let mut stream = TcpStream::connect(server)
+            .map_err(|e| format!("Unable to connect to {}: {}", server, e))?;
+        let channel = if channel.starts_with('#') {
+            channel.to_string()
+        } else {
+            format!("#{}", channel)
+        };
+        write!(
+            stream,
+            "NICK {}\r\nUSER {} 0 * :Color Shooter Battle\r\n",
+            nick, nick
+        )
+        .map_err(|e| format!("Unable to register with {}: {}", server, e))?;
+
+        let reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+        let (sender, commands) = mpsc::channel();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                let reply = match parse_line(&line) {
+                    // the server has accepted the nick
+                    ("001", _, _) => format!("JOIN {}\r\n", channel),
+                    ("PING", _, token) => format!("PONG :{}\r\n", token),
+                    ("PRIVMSG", user, text) => {
+                        if let Some(command) = Command::parse(text) {
+                            if sender.send((user.to_string(), command)).is_err() {
+                                return;
+                            }
+                        }
+                        continue;
+                    }
+                    _ => continue,
+                };
+                if stream.write_all(reply.as_bytes()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(ChatClient {
+            commands,
+            user_ready: HashMap::new(),
+            command_ready: HashMap::new(),
+            is_connected: true,
+        })
+    }
+
+    /// Applies the commands that arrived since the last call and are not rate limited.
+    pub fn apply(&mut self, sim: &mut Simulation) {
+        let now = Instant::now();
+        loop {
+            let (user, command) = match self.commands.try_recv() {
+                Ok(received) => received,
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.is_connected = false;
+                    break;
+                }
+            };
+            let is_ready = |ready: Option<&Instant>| ready.is_none_or(|t| *t <= now);
+            if !is_ready(self.user_ready.get(&user))
+                || !is_ready(self.command_ready.get(command.kind()))
+            {
+                continue;
+            }
+            command.apply(sim);
+            self.user_ready
+                .insert(user, now + ChatClient::USER_COOLDOWN);
+            self.command_ready
+                .insert(command.kind(), now + command.cooldown());
+        }
+        // forget people once they may chat again
+        self.user_ready.retain(|_, ready| *ready > now);
+    }
+
+    /// One line for the HUD, e.g. `chat !boost 12s  !drop ready`.
+    pub fn status(&self) -> String {
+        if !self.is_connected {
+            return "chat disconnected".to_string();
+        }
+        let now = Instant::now();
+        let mut status = "chat".to_string();
+        for command in [Command::Boost(1), Command::Drop(1)] {
+            let kind = command.kind();
+            match self.command_ready.get(kind).filter(|t| **t > now) {
+                Some(ready) => {
+                    let seconds = (*ready - now).as_secs_f64().ceil();
+                    status += &format!("  {} {}s", kind, seconds);
+                }
+                None => status += &format!("  {} ready", kind),
+            }
+        }
+        status
+    }
+}
+
+/// Splits an IRC line into its command, the nick that sent it and its last parameter, e.g.
+/// `:ann!a@host PRIVMSG #chan :!red` gives `("PRIVMSG", "ann", "!red")`.
+fn parse_line(line: &str) -> (&str, &str, &str) {
+    let (prefix, rest) = match line.strip_prefix(':') {
+        Some(rest) => rest.split_once(' ').unwrap_or((rest, "")),
+        None => ("", line),
+    };
+    let nick = prefix.split('!').next().unwrap_or("");
+    let command = rest.split(' ').next().unwrap_or("");
+    let last = match rest.split_once(" :") {
+        Some((_, trailing)) => trailing,
+        None => rest.rsplit(' ').next().unwrap_or(""),
+    };
+    (command, nick, last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_line_splits_prefix_and_trailing_parameter() {
+        assert_eq!(
+            parse_line(":nick!u@h PRIVMSG #c :!boost blue"),
+            ("PRIVMSG", "nick", "!boost blue")
+        );
+        assert_eq!(
+            parse_line("PING :irc.example.net"),
+            ("PING", "", "irc.example.net")
+        );
+        assert_eq!(parse_line("PING token"), ("PING", "", "token"));
+        assert_eq!(
+            parse_line(":irc.example.net 001 bot :Welcome"),
+            ("001", "irc.example.net", "Welcome")
+        );
+    }
+
+    #[test]
+    fn parse_reads_commands() {
+        assert!(matches!(
+            Command::parse("!boost blue"),
+            Some(Command::Boost(3))
+        ));
+        assert!(matches!(
+            Command::parse("!BOOST 2 please"),
+            Some(Command::Boost(2))
+        ));
+        assert!(matches!(Command::parse("!drop 4"), Some(Command::Drop(4))));
+        assert!(matches!(Command::parse("!yellow"), Some(Command::Cheer(4))));
+        assert!(matches!(Command::parse("!1"), Some(Command::Cheer(1))));
+    }
+
+    #[test]
+    fn parse_ignores_bad_commands() {
+        for text in [
+            "!drop 9",
+            "!drop 0",
+            "!drop",
+            "!boost purple",
+            "!purple",
+            "red",
+            "hello !red",
+            "",
+        ] {
+            assert!(Command::parse(text).is_none(), "{}", text);
+        }
+    }
+}
@@ -1,6 +1,8 @@
 pub const BACKGROUND: &str = "000000";
 pub const FRAME: &str = "222222";
 pub const GRID: &str = "111111";
+pub const HUD: &str = "FFFFFF";
+pub const DEBUG: &str = "FF8800";
 
 pub const FIRE_WELL: &str = "FF00FF";
 pub const MULTI_WELL: &str = "00FFFF";
@@ -16,3 +18,28 @@ pub const PLAYER3_CANNON: &str = "45818E";
 
 pub const PLAYER4_CANNON: &str = "FFFF00";
 pub const PLAYER4_FIELD: &str = "BF9000";
+
+pub fn field(owner: i8) -> &'static str {
+    match owner {
+        1 => PLAYER1_FIELD,
+        2 => PLAYER2_FIELD,
+        3 => PLAYER3_FIELD,
+        4 => PLAYER4_FIELD,
+        _ => BACKGROUND,
+    }
+}
+
+pub fn cannon(owner: i8) -> &'static str {
+    match owner {
+        1 => PLAYER1_CANNON,
+        2 => PLAYER2_CANNON,
+        3 => PLAYER3_CANNON,
+        4 => PLAYER4_CANNON,
+        _ => FRAME,
+    }
+}
+
+pub fn rgb(hex: &str) -> [u8; 3] {
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0);
+    [channel(0), channel(2), channel(4)]
+}
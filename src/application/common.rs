@@ -45,7 +45,6 @@ pub fn check_circle_boundary_collisions(
         if position[1] > (boundaries[1] + boundaries[3]) {
             new_angle = 360.0 - new_angle;
         }
-        println!("old: {} | new: {} => (x{}, y{}) r{} [x{}, y{}, w{}, h{}]", angle, new_angle, position[0], position[1], radius, boundaries[0], boundaries[1], boundaries[2], boundaries[3]);
     }
     new_angle
 }
@@ -0,0 +1,148 @@
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Response, Server};
+
+use super::common;
+use super::Simulation;
+
+/// Opt-in HTTP API on localhost for running the game from scripts and dashboards.
+///
+/// - `GET /status`: tick, seed, pause state, and each player's life, territory, loaded shots
+///   and plinko multiplier
+/// - `POST /load?player=2&shots=16`: loads shots into a cannon, 16 unless given
+/// - `POST /pause` and `POST /resume`: stop and restart the clock
+/// - `POST /restart?seed=42`: starts a new match, from a random seed unless given
+/// - `GET /grid`: the owner of every cell, row by row from the top
+///
+/// Requests are answered from the game loop, between ticks, so they always see a consistent
+/// match.
+pub struct ControlServer {
+    server: Server,
+}
+
+impl ControlServer {
+    const DEFAULT_SHOTS: i32 = 16;
+    const MAX_SHOTS: i32 = 1024;
+
+    pub fn start(port: u16) -> Result<ControlServer, String> {
+        let server = Server::http(("127.0.0.1", port))
+            .map_err(|e| format!("Unable to start the control API on port {}: {}", port, e))?;
+        Ok(ControlServer { server })
+    }
+
+    /// Answers every request that has arrived, changing the match or the pause state as asked.
+    pub fn handle(&mut self, sim: &mut Simulation, is_paused: &mut bool) {
+        while let Ok(Some(request)) = self.server.try_recv() {
+            let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+            let param = |name: &str| {
+                query
+                    .split('&')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            };
+            let result = match (request.method(), path) {
+                (Method::Get, "/status") => Ok(status(sim, *is_paused)),
+                (Method::Get, "/grid") => Ok(grid(sim)),
+                (Method::Post, "/load") => {
+                    load(sim, param("player"), param("shots")).map(|_| status(sim, *is_paused))
+                }
+                (Method::Post, "/pause") => {
+                    *is_paused = true;
+                    Ok(status(sim, *is_paused))
+                }
+                (Method::Post, "/resume") => {
+                    *is_paused = false;
+                    Ok(status(sim, *is_paused))
+                }
+                (Method::Post, "/restart") => {
+                    restart(sim, param("seed")).map(|_| status(sim, *is_paused))
+                }
+                (_, "/status" | "/grid" | "/load" | "/pause" | "/resume" | "/restart") => {
+                    Err((405, format!("{} is not allowed here", request.method())))
+                }
+                _ => Err((404, format!("Unknown path '{}'", path))),
+            };
+            let (code, body) = match result {
+                Ok(body) => (200, body),
+                Err((code, message)) => (code, json!({ "error": message })),
+            };
+            let header =
+                Header::from_bytes("Content-Type", "application/json").expect("Valid header");
+            let response = Response::from_string(body.to_string())
+                .with_status_code(code)
+                .with_header(header);
+            // a client that went away has nothing left to be told
+            let _ = request.respond(response);
+        }
+    }
+}
+
+fn status(sim: &Simulation, is_paused: bool) -> Value {
+    let players = sim
+        .cannons
+        .iter()
+        .map(|cannon| {
+            json!({
+                "id": cannon.id,
+                "alive": cannon.is_alive,
+                "territory": sim.grid.territory(cannon.id),
+                "loaded_shots": cannon.loaded_shots(),
+                "multiplier": sim.plinkos[(cannon.id - 1) as usize].shot_count(),
+            })
+        })
+        .collect::<Vec<Value>>();
+    json!({
+        "tick": sim.tick(),
+        "seed": sim.seed(),
+        "paused": is_paused,
+        "alive": sim.alive_count(),
+        "players": players,
+    })
+}
+
+fn grid(sim: &Simulation) -> Value {
+    let edge = common::CELL_EDGES * 2;
+    let rows = (0..edge)
+        .map(|y| {
+            (0..edge)
+                .map(|x| sim.grid.cells[common::calc_logical_index(x, y)])
+                .collect::<Vec<i8>>()
+        })
+        .collect::<Vec<Vec<i8>>>();
+    json!({ "size": edge, "cells": rows })
+}
+
+fn load(
+    sim: &mut Simulation,
+    player: Option<String>,
+    shots: Option<String>,
+) -> Result<(), (u16, String)> {
+    let player = player
+        .and_then(|p| p.parse::<i8>().ok())
+        .filter(|id| (1..=4).contains(id))
+        .ok_or((400, "player must be 1 to 4".to_string()))?;
+    let shots = match shots {
+        Some(shots) => shots
+            .parse::<i32>()
+            .ok()
+            .filter(|n| (1..=ControlServer::MAX_SHOTS).contains(n))
+            .ok_or((
+                400,
+                format!("shots must be 1 to {}", ControlServer::MAX_SHOTS),
+            ))?,
+        None => ControlServer::DEFAULT_SHOTS,
+    };
+    sim.load_cannon(player, shots);
+    Ok(())
+}
+
+fn restart(sim: &mut Simulation, seed: Option<String>) -> Result<(), (u16, String)> {
+    let seed = match seed {
+        Some(seed) => seed
+            .parse()
+            .map_err(|_| (400, "seed must be a number".to_string()))?,
+        None => rand::random(),
+    };
+    sim.restart(seed);
+    Ok(())
+}
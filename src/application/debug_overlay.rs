@@ -0,0 +1,133 @@
+use std::time::{Duration, Instant};
+
+use graphics::{Ellipse, Rectangle, Text, Transformed};
+use opengl_graphics::{GlGraphics, GlyphCache};
+
+use super::cannon::Cannon;
+use super::colors;
+use super::Simulation;
+
+/// Toggleable overlay with performance counters and the collision shapes used by the simulation.
+pub struct DebugOverlay {
+    pub is_visible: bool,
+    last_frame: Instant,
+    frame_seconds: f64,
+    update_seconds: f64,
+}
+
+impl DebugOverlay {
+    const TEXT_SIZE: u32 = 14;
+    const LINE_WIDTH: f64 = 1.0;
+    // exponential smoothing keeps the counters readable
+    const SMOOTHING: f64 = 0.1;
+    // velocity vectors are drawn as the distance covered in this many ticks
+    const VELOCITY_TICKS: f64 = 40.0;
+
+    pub fn new() -> DebugOverlay {
+        DebugOverlay {
+            is_visible: false,
+            last_frame: Instant::now(),
+            frame_seconds: 0.0,
+            update_seconds: 0.0,
+        }
+    }
+
+    pub fn record_frame(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_frame).as_secs_f64();
+        self.last_frame = now;
+        self.frame_seconds = DebugOverlay::smooth(self.frame_seconds, elapsed);
+    }
+
+    pub fn record_update(&mut self, elapsed: Duration) {
+        self.update_seconds = DebugOverlay::smooth(self.update_seconds, elapsed.as_secs_f64());
+    }
+
+    fn smooth(average: f64, sample: f64) -> f64 {
+        if average == 0.0 {
+            sample
+        } else {
+            average + (sample - average) * DebugOverlay::SMOOTHING
+        }
+    }
+
+    pub fn draw(
+        &self,
+        sim: &Simulation,
+        c: &graphics::Context,
+        gl: &mut GlGraphics,
+        glyphs: &mut GlyphCache,
+    ) {
+        let color = graphics::color::hex(colors::DEBUG);
+
+        let hit_radius = Cannon::hit_radius();
+        for cannon in sim.cannons.iter().filter(|c| c.is_alive) {
+            let [x, y] = cannon.position();
+            Ellipse::new_border(color, DebugOverlay::LINE_WIDTH).draw(
+                [
+                    x - hit_radius,
+                    y - hit_radius,
+                    hit_radius * 2.0,
+                    hit_radius * 2.0,
+                ],
+                &c.draw_state,
+                c.transform,
+                gl,
+            );
+        }
+
+        let outline = Rectangle::new_border(color, DebugOverlay::LINE_WIDTH);
+        let mut num_pucks = 0;
+        for plinko in &sim.plinkos {
+            for rect in [
+                plinko.get_fire_rect(),
+                plinko.get_multi_rect(),
+                plinko.get_divider_rect(),
+            ] {
+                outline.draw(rect, &c.draw_state, c.transform, gl);
+            }
+            for puck in plinko.pucks() {
+                let [x, y] = puck.position();
+                let [dx, dy] = puck.velocity();
+                graphics::line(
+                    color,
+                    DebugOverlay::LINE_WIDTH,
+                    [
+                        x,
+                        y,
+                        x + dx * DebugOverlay::VELOCITY_TICKS,
+                        y + dy * DebugOverlay::VELOCITY_TICKS,
+                    ],
+                    c.transform,
+                    gl,
+                );
+            }
+            num_pucks += plinko.pucks().len();
+        }
+
+        let fps = if self.frame_seconds > 0.0 {
+            1.0 / self.frame_seconds
+        } else {
+            0.0
+        };
+        let mut lines = vec![
+            format!(
+                "FPS {:.1}  update {:.3} ms",
+                fps,
+                self.update_seconds * 1000.0
+            ),
+            format!("bullets {}  pucks {}", sim.bullets.len(), num_pucks),
+        ];
+        lines.extend(sim.fire_report());
+        let field_rect = sim.field_rect();
+        let text = Text::new_color(color, DebugOverlay::TEXT_SIZE);
+        for (i, line) in lines.iter().enumerate() {
+            let text_transform = c.transform.trans(
+                field_rect[0] + 4.0,
+                field_rect[1] + (DebugOverlay::TEXT_SIZE as f64 * 1.2) * (i + 1) as f64,
+            );
+            text.draw(line, glyphs, &c.draw_state, text_transform, gl)
+                .expect("Unable to render text");
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use std::io::{self, BufRead, Write};
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::cannon::ManualControl;
+use super::common;
+use super::simulation::SeatInput;
+use super::Simulation;
+
+/// What a policy does with one cannon for a step, e.g. `{"angle": 120.0, "fire": true}`.
+#[derive(Clone, Copy, Deserialize)]
+pub struct Action {
+    pub angle: f64,
+    #[serde(default)]
+    pub fire: bool,
+}
+
+impl Action {
+    // far enough that the barrel points the same way wherever the target lies on the field
+    const TARGET_DISTANCE: f64 = 10_000.0;
+
+    /// The same action as a person at the seat would take it, turning the barrel towards the
+    /// angle and holding the trigger.
+    fn seat_input(&self, position: [f64; 2]) -> SeatInput {
+        let radians = self.angle.to_radians();
+        SeatInput {
+            manual: ManualControl {
+                is_firing: self.fire,
+                target: Some([
+                    position[0] + radians.cos() * Action::TARGET_DISTANCE,
+                    position[1] + radians.sin() * Action::TARGET_DISTANCE,
+                ]),
+                ..ManualControl::default()
+            },
+            drop_x: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CannonObservation {
+    id: i8,
+    position: [f64; 2],
+    angle: f64,
+    min_angle: f64,
+    max_angle: f64,
+    loaded_shots: i32,
+    alive: bool,
+}
+
+#[derive(Serialize)]
+struct PlinkoObservation {
+    id: i8,
+    shot_count: i32,
+    pucks: Vec<[f64; 2]>,
+}
+
+/// Everything a policy sees of the match.
+#[derive(Serialize)]
+pub struct Observation {
+    tick: u64,
+    // the owner of every cell as one digit, 0 for neutral, row by row from the top
+    cells: String,
+    cannons: Vec<CannonObservation>,
+    plinkos: Vec<PlinkoObservation>,
+}
+
+/// The outcome of one step.
+pub struct Step {
+    pub observation: Observation,
+    pub rewards: [f64; 4],
+    pub done: bool,
+}
+
+/// A match that a training loop drives a step at a time, without a window.
+///
+/// Each step holds the given actions for `ticks_per_step` ticks. A player's reward is the
+/// number of cells it gained, less those it lost, plus `KILL_REWARD` for each cannon it
+/// destroyed and minus `KILL_REWARD` when its own cannon is destroyed. The match is done when
+/// at most one cannon is left or after `max_ticks`.
+pub struct Environment {
+    sim: Simulation,
+    delta_time: f64,
+    ticks_per_step: u64,
+    max_ticks: u64,
+    territory: [usize; 4],
+    // how many of the eliminations of the match have been rewarded
+    rewarded: usize,
+}
+
+impl Environment {
+    pub const KILL_REWARD: f64 = 500.0;
+
+    pub fn new(
+        sim: Simulation,
+        delta_time: f64,
+        ticks_per_step: u64,
+        max_ticks: u64,
+    ) -> Environment {
+        let mut env = Environment {
+            sim,
+            delta_time,
+            ticks_per_step,
+            max_ticks,
+            territory: [0; 4],
+            rewarded: 0,
+        };
+        env.territory = env.territories();
+        env
+    }
+
+    /// Starts a new match from `seed`, keeping the strategies of the seats without actions.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.sim.restart(seed);
+        self.territory = self.territories();
+        self.rewarded = 0;
+        self.observe()
+    }
+
+    /// Advances the match with an action for each seat, where `None` leaves the cannon to
+    /// its aiming strategy and fire control.
+    pub fn step(&mut self, actions: &[Option<Action>; 4]) -> Step {
+        for (i, action) in actions.iter().enumerate() {
+            let id = (i + 1) as i8;
+            let position = self.sim.cannons[i].position();
+            let input = action.map(|action| action.seat_input(position));
+            self.sim.apply_input(id, input.as_ref());
+        }
+        for _ in 0..self.ticks_per_step {
+            if self.is_done() {
+                break;
+            }
+            self.sim.update(self.delta_time);
+        }
+
+        let territory = self.territories();
+        let mut rewards = [0.0; 4];
+        for i in 0..rewards.len() {
+            rewards[i] = territory[i] as f64 - self.territory[i] as f64;
+        }
+        self.territory = territory;
+        for elimination in &self.sim.eliminations()[self.rewarded..] {
+            rewards[(elimination.killer - 1) as usize] += Environment::KILL_REWARD;
+            rewards[(elimination.victim - 1) as usize] -= Environment::KILL_REWARD;
+        }
+        self.rewarded = self.sim.eliminations().len();
+
+        Step {
+            observation: self.observe(),
+            rewards,
+            done: self.is_done(),
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.sim.alive_count() <= 1 || self.sim.tick() >= self.max_ticks
+    }
+
+    fn territories(&self) -> [usize; 4] {
+        [1, 2, 3, 4].map(|id| self.sim.grid.territory(id))
+    }
+
+    fn observe(&self) -> Observation {
+        let edge = common::CELL_EDGES * 2;
+        let cells = (0..edge)
+            .flat_map(|y| (0..edge).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let owner = self.sim.grid.cells[common::calc_logical_index(x, y)];
+                char::from(b'0' + owner as u8)
+            })
+            .collect();
+        Observation {
+            tick: self.sim.tick(),
+            cells,
+            cannons: self
+                .sim
+                .cannons
+                .iter()
+                .map(|cannon| {
+                    let view = cannon.view();
+                    CannonObservation {
+                        id: view.id,
+                        position: view.position,
+                        angle: view.angle_deg,
+                        min_angle: view.min_angle_deg,
+                        max_angle: view.max_angle_deg,
+                        loaded_shots: view.loaded_shots,
+                        alive: view.is_alive,
+                    }
+                })
+                .collect(),
+            plinkos: self
+                .sim
+                .plinkos
+                .iter()
+                .enumerate()
+                .map(|(i, plinko)| PlinkoObservation {
+                    id: (i + 1) as i8,
+                    shot_count: plinko.shot_count(),
+                    pucks: plinko.pucks().iter().map(|p| p.position()).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A request to `serve`, one JSON object per line.
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Request {
+    // a seed per environment, or random ones
+    Reset { seeds: Option<Vec<u64>> },
+    // up to four actions per environment, `null` for seats left to their strategy
+    Step { actions: Vec<Vec<Option<Action>>> },
+}
+
+/// Runs the environments for a training loop on the other end of standard input and output.
+///
+/// `{"op": "reset", "seeds": [1, 2]}` answers with `{"observations": [...]}`, and
+/// `{"op": "step", "actions": [[{"angle": 90.0, "fire": true}, null], ...]}` with
+/// `{"observations": [...], "rewards": [[...], ...], "dones": [...]}`. The environments step
+/// in parallel, spread over the cores of the machine.
+pub fn serve(mut envs: Vec<Environment>) -> Result<(), String> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|e| format!("Unable to read a request: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let answer = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                answer(&mut envs, request).unwrap_or_else(|message| json!({ "error": message }))
+            }
+            Err(e) => json!({ "error": format!("Unable to read the request: {}", e) }),
+        };
+        writeln!(out, "{}", answer)
+            .and_then(|_| out.flush())
+            .map_err(|e| format!("Unable to send an answer: {}", e))?;
+    }
+    Ok(())
+}
+
+fn answer(envs: &mut [Environment], request: Request) -> Result<Value, String> {
+    match request {
+        Request::Reset { seeds } => {
+            let seeds = match seeds {
+                Some(seeds) if seeds.len() != envs.len() => {
+                    return Err(format!(
+                        "Expected {} seeds, got {}",
+                        envs.len(),
+                        seeds.len()
+                    ))
+                }
+                Some(seeds) => seeds,
+                None => envs.iter().map(|_| rand::random()).collect(),
+            };
+            let observations = envs
+                .iter_mut()
+                .zip(seeds)
+                .map(|(env, seed)| env.reset(seed))
+                .collect::<Vec<Observation>>();
+            Ok(json!({ "observations": observations }))
+        }
+        Request::Step { actions } => {
+            if actions.len() != envs.len() {
+                return Err(format!(
+                    "Expected actions for {} environments, got {}",
+                    envs.len(),
+                    actions.len()
+                ));
+            }
+            let mut seat_actions = Vec::with_capacity(actions.len());
+            for actions in actions {
+                if actions.len() > 4 {
+                    return Err(format!("Expected up to 4 actions, got {}", actions.len()));
+                }
+                let mut seats = [None; 4];
+                seats[..actions.len()].copy_from_slice(&actions);
+                seat_actions.push(seats);
+            }
+            let steps = step_all(envs, &seat_actions);
+            let rewards = steps.iter().map(|s| s.rewards).collect::<Vec<[f64; 4]>>();
+            let dones = steps.iter().map(|s| s.done).collect::<Vec<bool>>();
+            let observations = steps
+                .into_iter()
+                .map(|s| s.observation)
+                .collect::<Vec<Observation>>();
+            Ok(json!({ "observations": observations, "rewards": rewards, "dones": dones }))
+        }
+    }
+}
+
+/// Steps every environment, each group of them on its own thread.
+fn step_all(envs: &mut [Environment], actions: &[[Option<Action>; 4]]) -> Vec<Step> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let group = envs.len().div_ceil(threads).max(1);
+    thread::scope(|scope| {
+        let handles = envs
+            .chunks_mut(group)
+            .zip(actions.chunks(group))
+            .map(|(envs, actions)| {
+                scope.spawn(move || {
+                    envs.iter_mut()
+                        .zip(actions)
+                        .map(|(env, actions)| env.step(actions))
+                        .collect::<Vec<Step>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("An environment panicked"))
+            .collect()
+    })
+}
@@ -0,0 +1,108 @@
+use opengl_graphics::{CreateTexture, Filter, Format, Texture, TextureSettings, UpdateTexture};
+
+use super::colors;
+use super::common;
+use super::grid::Grid;
+
+/// Keeps the territory of the playing field in a texture, so only changed cells are redrawn.
+pub struct FieldTexture {
+    texture: Texture,
+    pixels: Vec<u8>,
+}
+
+impl FieldTexture {
+    const EDGE: usize = (common::CELL_EDGES * 2) as usize;
+    const SIZE: usize = FieldTexture::EDGE * common::CELL_WIDTH as usize;
+    // above this many dirty cells a single full upload is cheaper than one upload per cell
+    const FULL_UPLOAD_CELLS: usize = 256;
+
+    pub fn new() -> FieldTexture {
+        let pixels = vec![0; FieldTexture::SIZE * FieldTexture::SIZE * 4];
+        let texture = CreateTexture::create(
+            &mut (),
+            Format::Rgba8,
+            &pixels,
+            [FieldTexture::SIZE as u32, FieldTexture::SIZE as u32],
+            // keep cell edges crisp when the layout is scaled up
+            &TextureSettings::new().filter(Filter::Nearest),
+        )
+        .expect("Unable to create field texture");
+        FieldTexture { texture, pixels }
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// Repaints the cells that `grid` reports as changed since the last update.
+    pub fn update(&mut self, grid: &mut Grid) {
+        let dirty = grid.take_dirty();
+        for &index in &dirty {
+            self.paint_cell(index, grid.cells[index]);
+        }
+
+        if dirty.len() > FieldTexture::FULL_UPLOAD_CELLS {
+            let size = [FieldTexture::SIZE as u32, FieldTexture::SIZE as u32];
+            UpdateTexture::update(
+                &mut self.texture,
+                &mut (),
+                Format::Rgba8,
+                &self.pixels,
+                [0, 0],
+                size,
+            )
+            .expect("Unable to update field texture");
+        } else {
+            for &index in &dirty {
+                self.upload_cell(index);
+            }
+        }
+    }
+
+    fn cell_origin(index: usize) -> (usize, usize) {
+        // cells are stored column by column, see `common::calc_logical_index`
+        let width = common::CELL_WIDTH as usize;
+        (
+            (index / FieldTexture::EDGE) * width,
+            (index % FieldTexture::EDGE) * width,
+        )
+    }
+
+    fn paint_cell(&mut self, index: usize, owner: i8) {
+        let [r, g, b] = colors::rgb(colors::field(owner));
+        let [lr, lg, lb] = colors::rgb(colors::GRID);
+        let (left, top) = FieldTexture::cell_origin(index);
+        let width = common::CELL_WIDTH as usize;
+        for y in 0..width {
+            for x in 0..width {
+                // the top and left edge of each cell carry the grid lines
+                let color = if x == 0 || y == 0 {
+                    [lr, lg, lb, 255]
+                } else {
+                    [r, g, b, 255]
+                };
+                let offset = ((top + y) * FieldTexture::SIZE + left + x) * 4;
+                self.pixels[offset..offset + 4].copy_from_slice(&color);
+            }
+        }
+    }
+
+    fn upload_cell(&mut self, index: usize) {
+        let (left, top) = FieldTexture::cell_origin(index);
+        let width = common::CELL_WIDTH as usize;
+        let mut block = Vec::with_capacity(width * width * 4);
+        for y in 0..width {
+            let offset = ((top + y) * FieldTexture::SIZE + left) * 4;
+            block.extend_from_slice(&self.pixels[offset..offset + width * 4]);
+        }
+        UpdateTexture::update(
+            &mut self.texture,
+            &mut (),
+            Format::Rgba8,
+            &block,
+            [left as u32, top as u32],
+            [width as u32, width as u32],
+        )
+        .expect("Unable to update field texture");
+    }
+}
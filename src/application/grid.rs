@@ -1,13 +1,29 @@
+use image::{Rgba, RgbaImage};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::colors;
+
 pub struct Grid {
     pub cells: [i8; (super::common::CELL_EDGES * super::common::CELL_EDGES * 4) as usize],
+    // cells changed since the last call to `take_dirty`, each listed once
+    dirty: Vec<usize>,
+    is_dirty: Vec<bool>,
 }
 
 impl Grid {
-    pub fn new() -> Grid {
+    /// A quarter of the field for each player, except for `neutral_band` cells along the middle
+    /// both across and down.
+    pub fn new(neutral_band: i32) -> Grid {
         let mut c = [0; (super::common::CELL_EDGES * super::common::CELL_EDGES * 4) as usize];
+        let band = (super::common::CELL_EDGES - neutral_band / 2)
+            ..(super::common::CELL_EDGES - neutral_band / 2 + neutral_band);
         for y in 0..(super::common::CELL_EDGES * 2) {
             for x in 0..(super::common::CELL_EDGES * 2) {
                 let index = super::common::calc_logical_index(x, y);
+                if band.contains(&x) || band.contains(&y) {
+                    continue;
+                }
                 if x < super::common::CELL_EDGES && y < super::common::CELL_EDGES {
                     c[index] = 1;
                 } else if x >= super::common::CELL_EDGES && y < super::common::CELL_EDGES {
@@ -19,15 +35,156 @@ impl Grid {
                 }
             }
         }
-        Grid { cells: c }
+        Grid::from_cells(c)
+    }
+
+    /// A grid with the given owners, indexed like `common::calc_logical_index`.
+    pub fn from_cells(
+        cells: [i8; (super::common::CELL_EDGES * super::common::CELL_EDGES * 4) as usize],
+    ) -> Grid {
+        // a fresh grid has never been drawn, so every cell starts out dirty
+        Grid {
+            cells,
+            dirty: (0..cells.len()).collect(),
+            is_dirty: vec![true; cells.len()],
+        }
+    }
+
+    /// The owner of every cell as one string of digits per row, from the top.
+    pub fn rows(&self) -> Vec<String> {
+        (0..super::common::CELL_EDGES * 2)
+            .map(|y| {
+                (0..super::common::CELL_EDGES * 2)
+                    .map(|x| {
+                        let owner = self.cells[super::common::calc_logical_index(x, y)];
+                        char::from(b'0' + owner as u8)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The grid that `rows` describes, where each digit is a player from 1 to 4 or 0 for
+    /// neutral.
+    pub fn from_rows(rows: &[String]) -> Result<Grid, String> {
+        let edge = (super::common::CELL_EDGES * 2) as usize;
+        if rows.len() != edge || rows.iter().any(|row| row.len() != edge) {
+            return Err(format!("The grid must be {} rows of {} cells", edge, edge));
+        }
+        let mut cells = [0; (super::common::CELL_EDGES * super::common::CELL_EDGES * 4) as usize];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, owner) in row.bytes().enumerate() {
+                if !(b'0'..=b'4').contains(&owner) {
+                    return Err(format!(
+                        "Cells are owned by 0 to 4, got '{}' in row {}",
+                        owner as char,
+                        y + 1
+                    ));
+                }
+                cells[super::common::calc_logical_index(x as i32, y as i32)] = (owner - b'0') as i8;
+            }
+        }
+        Ok(Grid::from_cells(cells))
+    }
+
+    /// Saves the grid as a PNG with a pixel per cell in the color of its owner on the field.
+    pub fn save_image(&self, path: &str) -> Result<(), String> {
+        let edge = (super::common::CELL_EDGES * 2) as u32;
+        let image = RgbaImage::from_fn(edge, edge, |x, y| {
+            let owner = self.cells[super::common::calc_logical_index(x as i32, y as i32)];
+            let [r, g, b] = colors::rgb(colors::field(owner));
+            Rgba([r, g, b, 255])
+        });
+        image
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(|e| format!("Unable to write grid image '{}': {}", path, e))
+    }
+
+    /// The grid drawn in an image with a pixel per cell, as `save_image` writes it.
+    ///
+    /// Each pixel goes to the player whose field or cannon color is closest to it, or is
+    /// neutral when it is closer to black or white or mostly transparent, so a grid can be
+    /// painted with any of those colors.
+    pub fn load_image(path: &str) -> Result<Grid, String> {
+        let image = image::open(path)
+            .map_err(|e| format!("Unable to read grid image '{}': {}", path, e))?
+            .to_rgba8();
+        let edge = (super::common::CELL_EDGES * 2) as u32;
+        if image.dimensions() != (edge, edge) {
+            return Err(format!(
+                "Grid image '{}' must be {} by {} pixels, got {} by {}",
+                path,
+                edge,
+                edge,
+                image.width(),
+                image.height()
+            ));
+        }
+        let mut palette = vec![(0, colors::BACKGROUND), (0, colors::HUD)];
+        for owner in 1..=4 {
+            palette.push((owner, colors::field(owner)));
+            palette.push((owner, colors::cannon(owner)));
+        }
+        let palette = palette
+            .into_iter()
+            .map(|(owner, hex)| (owner, colors::rgb(hex)))
+            .collect::<Vec<(i8, [u8; 3])>>();
+        let mut cells = [0; (super::common::CELL_EDGES * super::common::CELL_EDGES * 4) as usize];
+        for (x, y, &Rgba([r, g, b, a])) in image.enumerate_pixels() {
+            if a < 128 {
+                continue;
+            }
+            let distance = |[pr, pg, pb]: [u8; 3]| {
+                let channel = |p: u8, c: u8| (p as i32 - c as i32).pow(2);
+                channel(pr, r) + channel(pg, g) + channel(pb, b)
+            };
+            let (owner, _) = palette
+                .iter()
+                .min_by_key(|(_, color)| distance(*color))
+                .expect("The palette has colors");
+            cells[super::common::calc_logical_index(x as i32, y as i32)] = *owner;
+        }
+        Ok(Grid::from_cells(cells))
     }
 
     pub fn check_collision(&mut self, x: f64, y: f64, cannon_id: i8) -> bool {
         let index = super::common::calc_physical_index(x, y);
         if self.cells[index] != cannon_id {
             self.cells[index] = cannon_id;
+            self.mark_dirty(index);
             return true;
         }
         return false;
     }
+
+    pub fn take_dirty(&mut self) -> Vec<usize> {
+        for &index in &self.dirty {
+            self.is_dirty[index] = false;
+        }
+        std::mem::take(&mut self.dirty)
+    }
+
+    fn mark_dirty(&mut self, index: usize) {
+        if !self.is_dirty[index] {
+            self.is_dirty[index] = true;
+            self.dirty.push(index);
+        }
+    }
+
+    pub fn territory(&self, cannon_id: i8) -> usize {
+        self.cells.iter().filter(|&&c| c == cannon_id).count()
+    }
+}
+
+/// Saved as `rows`, so that a grid in a file can be read and changed by hand.
+impl Serialize for Grid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.rows().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Grid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Grid, D::Error> {
+        Grid::from_rows(&Vec::<String>::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
 }
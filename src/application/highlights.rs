@@ -0,0 +1,272 @@
+use std::collections::VecDeque;
+
+use super::cannon::Cannon;
+use super::Simulation;
+
+mod clip;
+
+pub use clip::{export, ClipFormat};
+
+/// What kind of moment a `Moment` is.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    Kill,
+    // the kill that ends the match, leaving one player or none standing
+    FinalKill,
+    Swing,
+    CloseCall,
+    Jackpot,
+}
+
+impl Kind {
+    /// Short name used in the file names of clips.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Kill => "kill",
+            Kind::FinalKill => "final-kill",
+            Kind::Swing => "swing",
+            Kind::CloseCall => "close-call",
+            Kind::Jackpot => "jackpot",
+        }
+    }
+}
+
+/// A moment of a match worth seeing again.
+#[derive(Clone)]
+pub struct Moment {
+    // ticks into the recording, or of the match when followed live
+    pub position: usize,
+    // ticks it took to happen before `position`, e.g. for a swing in territory
+    pub duration: usize,
+    // the player it is about, whose color marks it
+    pub player: i8,
+    pub kind: Kind,
+    pub label: String,
+}
+
+impl Moment {
+    /// Whether it makes a highlight on its own, which every kind does but a kill that
+    /// doesn't end the match.
+    pub fn is_highlight(&self) -> bool {
+        self.kind != Kind::Kill
+    }
+}
+
+/// Follows a match tick by tick, noting kills, big swings in territory, bullets that only
+/// just miss a cannon and plinko jackpots.
+pub struct Watch {
+    eliminations: usize,
+    shot_counts: [i32; 4],
+    // territory of each player every `SAMPLE_TICKS`, oldest first
+    samples: VecDeque<[usize; 4]>,
+    next_sample: u64,
+    // samples to wait before a player's next swing counts, so one swing is noted once
+    cooldowns: [usize; 4],
+    // near misses wait to see whether the cannon survives, see `CONFIRM_TICKS`
+    near_misses: Vec<Moment>,
+    // position of the last near miss of each cannon
+    last_near_miss: [Option<usize>; 4],
+}
+
+impl Watch {
+    const SAMPLE_TICKS: u64 = 60;
+    // a swing is counted over this many samples, five seconds
+    const SWING_SAMPLES: usize = 10;
+    const SWING_CELLS: usize = 80;
+    const JACKPOT_SHOTS: i32 = 32;
+    // how far outside the reach of a cannon a bullet passes for a close call
+    const NEAR_MISS_DISTANCE: f64 = 10.0;
+    // a cannon destroyed this soon after a near miss wasn't a close call after all
+    const CONFIRM_TICKS: usize = 60;
+    // a cannon under fire for a while has one close call
+    const NEAR_MISS_COOLDOWN: usize = 240;
+
+    pub fn new(sim: &Simulation) -> Watch {
+        Watch {
+            eliminations: sim.eliminations().len(),
+            shot_counts: sim.plinkos.each_ref().map(|p| p.shot_count()),
+            samples: VecDeque::from([Watch::territory(sim)]),
+            next_sample: sim.tick() + Watch::SAMPLE_TICKS,
+            cooldowns: [0; 4],
+            near_misses: Vec::new(),
+            last_near_miss: [None; 4],
+        }
+    }
+
+    fn territory(sim: &Simulation) -> [usize; 4] {
+        [1, 2, 3, 4].map(|id| sim.grid.territory(id))
+    }
+
+    /// Adds the moments of the tick that brought the match to `position`. Close calls are
+    /// added a little later, once it is clear the cannon survived.
+    pub fn observe(&mut self, sim: &Simulation, position: usize, moments: &mut Vec<Moment>) {
+        for (i, elimination) in sim
+            .eliminations()
+            .iter()
+            .enumerate()
+            .skip(self.eliminations)
+        {
+            // bullets in flight can destroy the last two cannons in the same tick
+            let is_final = i + 1 == sim.eliminations().len() && sim.alive_count() <= 1;
+            moments.push(Moment {
+                position,
+                duration: 0,
+                player: elimination.killer,
+                kind: if is_final {
+                    Kind::FinalKill
+                } else {
+                    Kind::Kill
+                },
+                label: if is_final && sim.alive_count() == 0 {
+                    format!(
+                        "player {} destroyed by player {}, leaving no one standing",
+                        elimination.victim, elimination.killer
+                    )
+                } else if is_final {
+                    format!(
+                        "player {} wins by destroying player {}",
+                        elimination.killer, elimination.victim
+                    )
+                } else {
+                    format!(
+                        "player {} destroyed by player {}",
+                        elimination.victim, elimination.killer
+                    )
+                },
+            });
+        }
+        self.eliminations = sim.eliminations().len();
+
+        // the multiplier only ever goes down when it is paid out
+        for (i, plinko) in sim.plinkos.iter().enumerate() {
+            let before = self.shot_counts[i];
+            if plinko.shot_count() < before && before >= Watch::JACKPOT_SHOTS {
+                moments.push(Moment {
+                    position,
+                    duration: 0,
+                    player: (i + 1) as i8,
+                    kind: Kind::Jackpot,
+                    label: format!("player {} jackpot of {} shots", i + 1, before),
+                });
+            }
+            self.shot_counts[i] = plinko.shot_count();
+        }
+
+        self.watch_near_misses(sim, position, moments);
+
+        if sim.tick() < self.next_sample {
+            return;
+        }
+        self.next_sample = sim.tick() + Watch::SAMPLE_TICKS;
+        let now = Watch::territory(sim);
+        for i in 0..4 {
+            self.cooldowns[i] = self.cooldowns[i].saturating_sub(1);
+            let (gain, samples_ago) = self
+                .samples
+                .iter()
+                .rev()
+                .enumerate()
+                .map(|(ago, s)| (now[i].saturating_sub(s[i]), ago + 1))
+                .max()
+                .unwrap_or((0, 0));
+            if self.cooldowns[i] == 0 && gain >= Watch::SWING_CELLS {
+                moments.push(Moment {
+                    position,
+                    duration: samples_ago * Watch::SAMPLE_TICKS as usize,
+                    player: (i + 1) as i8,
+                    kind: Kind::Swing,
+                    label: format!("player {} took {} cells", i + 1, gain),
+                });
+                self.cooldowns[i] = Watch::SWING_SAMPLES;
+            }
+        }
+        self.samples.push_back(now);
+        if self.samples.len() > Watch::SWING_SAMPLES {
+            self.samples.pop_front();
+        }
+    }
+
+    fn watch_near_misses(&mut self, sim: &Simulation, position: usize, moments: &mut Vec<Moment>) {
+        for cannon in sim.cannons.iter().filter(|c| c.is_alive) {
+            let i = (cannon.id - 1) as usize;
+            if self.last_near_miss[i]
+                .is_some_and(|last| position < last + Watch::NEAR_MISS_COOLDOWN)
+            {
+                continue;
+            }
+            let [x, y] = cannon.position();
+            let shooter = sim.bullets.iter().find(|b| {
+                let distance = (b.position[0] - x).hypot(b.position[1] - y);
+                b.cannon_id != cannon.id
+                    && distance < Cannon::hit_radius() + Watch::NEAR_MISS_DISTANCE
+            });
+            if let Some(bullet) = shooter {
+                self.last_near_miss[i] = Some(position);
+                self.near_misses.push(Moment {
+                    position,
+                    duration: 0,
+                    player: cannon.id,
+                    kind: Kind::CloseCall,
+                    label: format!(
+                        "player {} narrowly missed by player {}",
+                        cannon.id, bullet.cannon_id
+                    ),
+                });
+            }
+        }
+        let (confirmed, waiting) = self
+            .near_misses
+            .drain(..)
+            .partition::<Vec<Moment>, _>(|m| position >= m.position + Watch::CONFIRM_TICKS);
+        self.near_misses = waiting;
+        moments.extend(
+            confirmed
+                .into_iter()
+                .filter(|m| sim.cannons[(m.player - 1) as usize].is_alive),
+        );
+    }
+
+    /// Adds the close calls still waiting when the match ends where `sim` stands.
+    pub fn finish(&mut self, sim: &Simulation, moments: &mut Vec<Moment>) {
+        moments.extend(
+            self.near_misses
+                .drain(..)
+                .filter(|m| sim.cannons[(m.player - 1) as usize].is_alive),
+        );
+    }
+}
+
+/// Prints the highlights of a match as it is played.
+pub struct Highlights {
+    watch: Option<Watch>,
+    tick: u64,
+    moments: Vec<Moment>,
+}
+
+impl Highlights {
+    pub fn new() -> Highlights {
+        Highlights {
+            watch: None,
+            tick: 0,
+            moments: Vec::new(),
+        }
+    }
+
+    /// Looks at the match after an update, starting over when it went back in time, e.g.
+    /// after a restart.
+    pub fn observe(&mut self, sim: &Simulation) {
+        let watch = match &mut self.watch {
+            Some(watch) if sim.tick() >= self.tick => watch,
+            _ => self.watch.insert(Watch::new(sim)),
+        };
+        self.tick = sim.tick();
+        watch.observe(sim, sim.tick() as usize, &mut self.moments);
+        for moment in self.moments.drain(..).filter(Moment::is_highlight) {
+            println!(
+                "Highlight at {:.1}s: {}",
+                moment.position as f64 / super::timeline::TICKS_PER_SECOND as f64,
+                moment.label
+            );
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::fs::{self, File};
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, Rgba, RgbaImage};
+
+use super::super::bullet::Bullet;
+use super::super::cannon::Cannon;
+use super::super::colors;
+use super::super::common;
+use super::super::replay::Replay;
+use super::super::timeline;
+use super::super::Simulation;
+use super::Moment;
+
+/// How clips are saved.
+#[derive(Clone, Copy)]
+pub enum ClipFormat {
+    // one animated GIF per clip
+    Gif,
+    // a directory of numbered PNG frames per clip, for a video editor
+    Png,
+}
+
+impl ClipFormat {
+    pub fn by_name(name: &str) -> Option<ClipFormat> {
+        match name {
+            "gif" => Some(ClipFormat::Gif),
+            "png" => Some(ClipFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+// seconds of play shown before and after each moment
+const PADDING_TICKS: usize = 3 * timeline::TICKS_PER_SECOND as usize;
+const FRAMES_PER_SECOND: u32 = 10;
+const FRAME_TICKS: usize = (timeline::TICKS_PER_SECOND / FRAMES_PER_SECOND as u64) as usize;
+// pixels per cell, half the size of the window
+const CELL_PIXELS: u32 = common::CELL_WIDTH as u32 / 2;
+const SCALE: f64 = CELL_PIXELS as f64 / common::CELL_WIDTH as f64;
+
+/// Saves a clip of every highlight of a recording in `dir`, each with a few seconds of play
+/// before and after, and returns the paths written.
+pub fn export(replay: &mut Replay, dir: &str, format: ClipFormat) -> Result<Vec<String>, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Unable to create '{}': {}", dir, e))?;
+    let highlights = replay
+        .bookmarks()
+        .iter()
+        .filter(|m| m.is_highlight())
+        .cloned()
+        .collect::<Vec<Moment>>();
+    let mut paths = Vec::new();
+    for (number, moment) in highlights.iter().enumerate() {
+        let name = format!("{}/highlight-{:02}-{}", dir, number + 1, moment.kind.name());
+        let frames = frames(replay, moment);
+        let path = match format {
+            ClipFormat::Gif => save_gif(&name, frames)?,
+            ClipFormat::Png => save_pngs(&name, &frames)?,
+        };
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Frames of the field from `PADDING_TICKS` before the moment began to as long after it.
+fn frames(replay: &mut Replay, moment: &Moment) -> Vec<RgbaImage> {
+    let start = moment
+        .position
+        .saturating_sub(moment.duration + PADDING_TICKS);
+    let end = (moment.position + PADDING_TICKS).min(replay.end());
+    let mut sim = replay.seek_to(start);
+    let mut frames = vec![render(&sim)];
+    while replay.position() < end {
+        replay.advance(&mut sim);
+        if (replay.position() - start).is_multiple_of(FRAME_TICKS) {
+            frames.push(render(&sim));
+        }
+    }
+    frames
+}
+
+fn save_gif(name: &str, frames: Vec<RgbaImage>) -> Result<String, String> {
+    let path = format!("{}.gif", name);
+    let error = |e: String| format!("Unable to write clip '{}': {}", path, e);
+    let file = File::create(&path).map_err(|e| error(e.to_string()))?;
+    // the fastest palette search is plenty for the few flat colors of the field
+    let mut encoder = GifEncoder::new_with_speed(file, 30);
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .map_err(|e| error(e.to_string()))?;
+    let delay = Delay::from_numer_denom_ms(1000, FRAMES_PER_SECOND);
+    encoder
+        .encode_frames(
+            frames
+                .into_iter()
+                .map(|image| Frame::from_parts(image, 0, 0, delay)),
+        )
+        .map_err(|e| error(e.to_string()))?;
+    Ok(path)
+}
+
+fn save_pngs(name: &str, frames: &[RgbaImage]) -> Result<String, String> {
+    fs::create_dir_all(name).map_err(|e| format!("Unable to create '{}': {}", name, e))?;
+    for (number, image) in frames.iter().enumerate() {
+        let path = format!("{}/frame-{:04}.png", name, number + 1);
+        image
+            .save(&path)
+            .map_err(|e| format!("Unable to write frame '{}': {}", path, e))?;
+    }
+    Ok(name.to_string())
+}
+
+/// Draws the playing field with its cannons and bullets, without a window to draw in.
+pub fn render(sim: &Simulation) -> RgbaImage {
+    let edge = (common::CELL_EDGES * 2) as u32;
+    let size = edge * CELL_PIXELS;
+    let grid_line = pixel(colors::GRID);
+    let mut image = RgbaImage::from_pixel(size, size, grid_line);
+    for (index, owner) in sim.grid.cells.iter().enumerate() {
+        // cells are stored column by column, see `common::calc_logical_index`
+        let (left, top) = (
+            index as u32 / edge * CELL_PIXELS,
+            index as u32 % edge * CELL_PIXELS,
+        );
+        let color = pixel(colors::field(*owner));
+        // the top and left edge of each cell carry the grid lines
+        for y in top + 1..top + CELL_PIXELS {
+            for x in left + 1..left + CELL_PIXELS {
+                image.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    let [field_x, field_y, _, _] = sim.field_rect();
+    let to_image = |[x, y]: [f64; 2]| [(x - field_x) * SCALE, (y - field_y) * SCALE];
+    for cannon in sim.cannons.iter().filter(|c| c.is_alive) {
+        let color = pixel(colors::cannon(cannon.id));
+        let center = to_image(cannon.position());
+        let radius = Cannon::radius() * SCALE;
+        fill_circle(&mut image, center, radius, color);
+        // the barrel as a row of dots out to one and a half times the radius
+        let angle = cannon.view().angle_deg.to_radians();
+        for step in 0..=6 {
+            let reach = radius * 1.5 * step as f64 / 6.0;
+            let point = [
+                center[0] + reach * angle.cos(),
+                center[1] + reach * angle.sin(),
+            ];
+            fill_circle(&mut image, point, radius / 3.0, color);
+        }
+    }
+    for bullet in &sim.bullets {
+        let color = pixel(colors::cannon(bullet.cannon_id));
+        let radius = Bullet::RADIUS * SCALE;
+        fill_circle(&mut image, to_image(bullet.position), radius, color);
+    }
+    image
+}
+
+fn pixel(hex: &str) -> Rgba<u8> {
+    let [r, g, b] = colors::rgb(hex);
+    Rgba([r, g, b, 255])
+}
+
+fn fill_circle(image: &mut RgbaImage, center: [f64; 2], radius: f64, color: Rgba<u8>) {
+    let (width, height) = image.dimensions();
+    let left = (center[0] - radius).floor().max(0.0) as u32;
+    let top = (center[1] - radius).floor().max(0.0) as u32;
+    let right = ((center[0] + radius).ceil().max(0.0) as u32).min(width);
+    let bottom = ((center[1] + radius).ceil().max(0.0) as u32).min(height);
+    for y in top..bottom {
+        for x in left..right {
+            let dx = x as f64 + 0.5 - center[0];
+            let dy = y as f64 + 0.5 - center[1];
+            if dx.hypot(dy) <= radius {
+                image.put_pixel(x, y, color);
+            }
+        }
+    }
+}
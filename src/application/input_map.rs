@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::fs;
+
+use piston::{Button, Key, MouseButton};
+
+/// Everything a key or mouse button can be bound to.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    LoadShots(i8),
+    Pause,
+    Step,
+    SlowDown,
+    SpeedUp,
+    ToggleDebug,
+    ToggleFullscreen,
+    RotateCcw(i8),
+    RotateCw(i8),
+    Fire(i8),
+    Click,
+    Screenshot,
+    Restart,
+    SaveSnapshot,
+    LoadSnapshot,
+    SaveGrid,
+    Rewind,
+    NextBookmark,
+    PreviousBookmark,
+}
+
+impl Action {
+    pub fn all() -> Vec<Action> {
+        let mut actions = vec![
+            Action::Pause,
+            Action::Step,
+            Action::SlowDown,
+            Action::SpeedUp,
+            Action::ToggleDebug,
+            Action::ToggleFullscreen,
+            Action::Click,
+            Action::Screenshot,
+            Action::Restart,
+            Action::SaveSnapshot,
+            Action::LoadSnapshot,
+            Action::SaveGrid,
+            Action::Rewind,
+            Action::NextBookmark,
+            Action::PreviousBookmark,
+        ];
+        for id in 1..=4 {
+            actions.extend([
+                Action::LoadShots(id),
+                Action::RotateCcw(id),
+                Action::RotateCw(id),
+                Action::Fire(id),
+            ]);
+        }
+        actions
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Action::LoadShots(id) => format!("load_shots_{}", id),
+            Action::Pause => "pause".to_string(),
+            Action::Step => "step".to_string(),
+            Action::SlowDown => "slow_down".to_string(),
+            Action::SpeedUp => "speed_up".to_string(),
+            Action::ToggleDebug => "debug_overlay".to_string(),
+            Action::ToggleFullscreen => "fullscreen".to_string(),
+            Action::RotateCcw(id) => format!("rotate_ccw_{}", id),
+            Action::RotateCw(id) => format!("rotate_cw_{}", id),
+            Action::Fire(id) => format!("fire_{}", id),
+            Action::Click => "click".to_string(),
+            Action::Screenshot => "screenshot".to_string(),
+            Action::Restart => "restart".to_string(),
+            Action::SaveSnapshot => "save_snapshot".to_string(),
+            Action::LoadSnapshot => "load_snapshot".to_string(),
+            Action::SaveGrid => "save_grid".to_string(),
+            Action::Rewind => "rewind".to_string(),
+            Action::NextBookmark => "next_bookmark".to_string(),
+            Action::PreviousBookmark => "previous_bookmark".to_string(),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::all().into_iter().find(|a| a.name() == name)
+    }
+}
+
+/// Maps keys and mouse buttons to actions, starting from defaults that a config file can change.
+///
+/// Each line of the config file binds an action to one or more buttons, replacing its default
+/// bindings, e.g. `fire_1 = Space, MouseRight`. Keys use their piston names, such as `A`, `D1`,
+/// `Left` or `NumPad4`, and mouse buttons are written `MouseLeft`, `MouseRight` and so on. An
+/// action bound to nothing (`restart =`) is disabled. Lines starting with `#` are ignored.
+pub struct InputMap {
+    bindings: HashMap<Button, Action>,
+}
+
+impl InputMap {
+    pub fn defaults() -> Vec<(Action, Vec<Button>)> {
+        let key = |k: Key| vec![Button::Keyboard(k)];
+        vec![
+            (Action::LoadShots(1), key(Key::D1)),
+            (Action::LoadShots(2), key(Key::D2)),
+            (Action::LoadShots(3), key(Key::D3)),
+            (Action::LoadShots(4), key(Key::D4)),
+            (Action::Pause, key(Key::Space)),
+            (Action::Step, key(Key::Period)),
+            (Action::SlowDown, key(Key::Minus)),
+            (Action::SpeedUp, key(Key::Equals)),
+            (Action::ToggleDebug, key(Key::F3)),
+            (Action::ToggleFullscreen, key(Key::F11)),
+            (Action::RotateCcw(1), key(Key::A)),
+            (Action::RotateCw(1), key(Key::D)),
+            (Action::Fire(1), key(Key::W)),
+            (Action::RotateCcw(2), key(Key::Left)),
+            (Action::RotateCw(2), key(Key::Right)),
+            (Action::Fire(2), key(Key::Up)),
+            (Action::RotateCcw(3), key(Key::J)),
+            (Action::RotateCw(3), key(Key::L)),
+            (Action::Fire(3), key(Key::I)),
+            (Action::RotateCcw(4), key(Key::NumPad4)),
+            (Action::RotateCw(4), key(Key::NumPad6)),
+            (Action::Fire(4), key(Key::NumPad8)),
+            (Action::Click, vec![Button::Mouse(MouseButton::Left)]),
+            (Action::Screenshot, key(Key::F12)),
+            (Action::Restart, key(Key::F5)),
+            (Action::SaveSnapshot, key(Key::F6)),
+            (Action::LoadSnapshot, key(Key::F9)),
+            (Action::SaveGrid, key(Key::F7)),
+            (Action::Rewind, key(Key::Backspace)),
+            (Action::NextBookmark, key(Key::PageDown)),
+            (Action::PreviousBookmark, key(Key::PageUp)),
+        ]
+    }
+
+    pub fn new() -> InputMap {
+        InputMap::build(InputMap::defaults()).expect("Default bindings conflict")
+    }
+
+    pub fn load(path: &str) -> Result<InputMap, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read controls from {}: {}", path, e))?;
+        InputMap::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(text: &str) -> Result<InputMap, String> {
+        let mut bindings = InputMap::defaults();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| format!("line {}: {}", number + 1, message);
+            let (name, buttons) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("expected <action> = <buttons>, got '{}'", line)))?;
+            let action = Action::from_name(name.trim())
+                .ok_or_else(|| error(format!("unknown action '{}'", name.trim())))?;
+            let buttons = buttons
+                .split(',')
+                .map(str::trim)
+                .filter(|b| !b.is_empty())
+                .map(|b| parse_button(b).ok_or_else(|| error(format!("unknown button '{}'", b))))
+                .collect::<Result<Vec<Button>, String>>()?;
+            match bindings.iter_mut().find(|(a, _)| *a == action) {
+                Some(binding) => binding.1 = buttons,
+                None => bindings.push((action, buttons)),
+            }
+        }
+        InputMap::build(bindings)
+    }
+
+    /// Fails with every button that ends up bound to more than one action.
+    fn build(bindings: Vec<(Action, Vec<Button>)>) -> Result<InputMap, String> {
+        let mut map = HashMap::new();
+        let mut conflicts = Vec::new();
+        for (action, buttons) in bindings {
+            for button in buttons {
+                // a button listed twice for the same action is no conflict
+                if let Some(other) = map.insert(button, action).filter(|other| *other != action) {
+                    conflicts.push(format!(
+                        "{} is bound to both {} and {}",
+                        button_name(&button),
+                        other.name(),
+                        action.name()
+                    ));
+                }
+            }
+        }
+        if conflicts.is_empty() {
+            Ok(InputMap { bindings: map })
+        } else {
+            Err(format!("conflicting bindings: {}", conflicts.join("; ")))
+        }
+    }
+
+    pub fn action(&self, button: &Button) -> Option<Action> {
+        self.bindings.get(button).copied()
+    }
+}
+
+fn parse_button(name: &str) -> Option<Button> {
+    if let Some(mouse) = name.strip_prefix("Mouse") {
+        return (1..=8)
+            .map(MouseButton::from)
+            .find(|b| format!("{:?}", b) == mouse)
+            .map(Button::Mouse);
+    }
+    // key codes are either ASCII or in the 0x4000_0000 range, see `piston::Key`
+    (0..0x80)
+        .chain(0x4000_0000..0x4000_0200)
+        .map(Key::from)
+        .filter(|k| *k != Key::Unknown)
+        .find(|k| format!("{:?}", k) == name)
+        .map(Button::Keyboard)
+}
+
+fn button_name(button: &Button) -> String {
+    match button {
+        Button::Keyboard(key) => format!("{:?}", key),
+        Button::Mouse(mouse) => format!("Mouse{:?}", mouse),
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_replaces_defaults() {
+        let map = InputMap::parse("# controls\nfire_1 = Space, MouseRight\npause = P\n").unwrap();
+        let fire = Some(Action::Fire(1));
+        assert_eq!(map.action(&Button::Keyboard(Key::Space)), fire);
+        assert_eq!(map.action(&Button::Mouse(MouseButton::Right)), fire);
+        assert_eq!(map.action(&Button::Keyboard(Key::W)), None);
+        assert_eq!(map.action(&Button::Keyboard(Key::P)), Some(Action::Pause));
+        assert_eq!(
+            map.action(&Button::Keyboard(Key::A)),
+            Some(Action::RotateCcw(1))
+        );
+    }
+
+    #[test]
+    fn parse_disables_an_action_bound_to_nothing() {
+        let map = InputMap::parse("restart =").unwrap();
+        assert_eq!(map.action(&Button::Keyboard(Key::F5)), None);
+    }
+
+    #[test]
+    fn parse_rejects_unknown_names() {
+        let error = InputMap::parse("\nfire_9 = W").err().unwrap();
+        assert_eq!(error, "line 2: unknown action 'fire_9'");
+        let error = InputMap::parse("fire_1 = Nope").err().unwrap();
+        assert_eq!(error, "line 1: unknown button 'Nope'");
+        let error = InputMap::parse("fire_1 W").err().unwrap();
+        assert_eq!(
+            error,
+            "line 1: expected <action> = <buttons>, got 'fire_1 W'"
+        );
+    }
+
+    #[test]
+    fn parse_reports_conflicts() {
+        let error = InputMap::parse("fire_1 = Space").err().unwrap();
+        assert_eq!(
+            error,
+            "conflicting bindings: Space is bound to both pause and fire_1"
+        );
+        // moving the other binding away resolves it
+        assert!(InputMap::parse("fire_1 = Space\npause = P").is_ok());
+    }
+
+    #[test]
+    fn parse_allows_a_button_listed_twice() {
+        let map = InputMap::parse("fire_1 = W, W").unwrap();
+        assert_eq!(map.action(&Button::Keyboard(Key::W)), Some(Action::Fire(1)));
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::BTreeMap;
+use std::thread;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::balance::ARRANGEMENTS;
+use super::Simulation;
+
+/// How one player has done on the ladder.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Standing {
+    pub rating: f64,
+    pub matches: u32,
+    pub wins: u32,
+}
+
+impl Default for Standing {
+    fn default() -> Standing {
+        Standing {
+            rating: Ladder::INITIAL_RATING,
+            matches: 0,
+            wins: 0,
+        }
+    }
+}
+
+/// Elo ratings of named players, kept in a local file between seasons.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Ladder {
+    players: BTreeMap<String, Standing>,
+}
+
+impl Ladder {
+    const INITIAL_RATING: f64 = 1500.0;
+    // the most a rating moves in one match
+    const K: f64 = 32.0;
+
+    /// Reads the ladder from `path`, or starts an empty one when there is no such file.
+    pub fn load(path: &str) -> Result<Ladder, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|e| format!("Invalid ladder in '{}': {}", path, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Ladder::default()),
+            Err(e) => Err(format!("Unable to read ladder '{}': {}", path, e)),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).expect("Ratings are plain numbers");
+        std::fs::write(path, text + "\n")
+            .map_err(|e| format!("Unable to write ladder '{}': {}", path, e))
+    }
+
+    /// Rates a match from its finishing order, winner first.
+    ///
+    /// Every pair of players counts as a game won by whoever finished ahead, and each player's
+    /// rating moves by `K` times its share of the difference between those results and what
+    /// the ratings expected.
+    pub fn record(&mut self, order: &[String]) {
+        let ratings = order
+            .iter()
+            .map(|name| {
+                self.players
+                    .get(name)
+                    .map_or(Ladder::INITIAL_RATING, |s| s.rating)
+            })
+            .collect::<Vec<f64>>();
+        let opponents = (order.len() - 1).max(1) as f64;
+        for (i, name) in order.iter().enumerate() {
+            let mut change = 0.0;
+            for (j, rating) in ratings.iter().enumerate() {
+                if i != j {
+                    let expected = 1.0 / (1.0 + 10f64.powf((rating - ratings[i]) / 400.0));
+                    let score = if i < j { 1.0 } else { 0.0 };
+                    change += score - expected;
+                }
+            }
+            let standing = self.players.entry(name.clone()).or_default();
+            standing.rating += Ladder::K * change / opponents;
+            standing.matches += 1;
+            if i == 0 {
+                standing.wins += 1;
+            }
+        }
+    }
+
+    /// One line per player, best rated first.
+    pub fn leaderboard(&self) -> Vec<String> {
+        let mut standings = self.players.iter().collect::<Vec<_>>();
+        standings.sort_by(|a, b| b.1.rating.total_cmp(&a.1.rating));
+        let mut lines = vec!["rank  rating  matches  wins  player".to_string()];
+        for (rank, (name, standing)) in standings.iter().enumerate() {
+            lines.push(format!(
+                "{:>4}  {:>6.1}  {:>7}  {:>4}  {}",
+                rank + 1,
+                standing.rating,
+                standing.matches,
+                standing.wins,
+                name
+            ));
+        }
+        lines
+    }
+}
+
+/// How a round-robin season is run.
+pub struct Season {
+    // matches each group of entrants plays, in turn through the arrangements of seats
+    pub rounds: usize,
+    pub seed: u64,
+    pub delta_time: f64,
+    // matches that last longer are placed as they stand
+    pub max_ticks: u64,
+}
+
+/// One match of a season: who sits where, and the seed it is played from.
+pub struct Fixture {
+    pub seed: u64,
+    // indices into the entrants, by seat
+    pub seats: Vec<(i8, usize)>,
+}
+
+/// Every group of up to four of the entrants meets `season.rounds` times, sitting in a
+/// different arrangement each time.
+pub fn schedule(season: &Season, entrants: usize) -> Vec<Fixture> {
+    let size = entrants.min(4);
+    let mut rng = StdRng::seed_from_u64(season.seed);
+    let mut fixtures = Vec::new();
+    for group in combinations(entrants, size) {
+        for round in 0..season.rounds {
+            let arrangement = ARRANGEMENTS[round % ARRANGEMENTS.len()];
+            fixtures.push(Fixture {
+                seed: rng.gen(),
+                seats: group
+                    .iter()
+                    .enumerate()
+                    .map(|(i, entrant)| (arrangement[i], *entrant))
+                    .collect(),
+            });
+        }
+    }
+    fixtures
+}
+
+/// All ways of choosing `size` of `0..count`, in order.
+fn combinations(count: usize, size: usize) -> Vec<Vec<usize>> {
+    if size == 0 {
+        return vec![Vec::new()];
+    }
+    (size - 1..count)
+        .flat_map(|last| {
+            combinations(last, size - 1)
+                .into_iter()
+                .map(move |mut group| {
+                    group.push(last);
+                    group
+                })
+        })
+        .collect()
+}
+
+/// Plays the fixtures in parallel, spread over the cores of the machine, and returns the
+/// finishing order of the entrants in each, winner first.
+///
+/// Seats that no entrant sits in are left to the default strategies, which aren't rated.
+pub fn play<F>(
+    season: &Season,
+    fixtures: &[Fixture],
+    new_match: F,
+) -> Result<Vec<Vec<usize>>, String>
+where
+    F: Fn(&Fixture) -> Result<Simulation, String> + Sync,
+{
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let group = fixtures.len().div_ceil(threads).max(1);
+    let new_match = &new_match;
+    thread::scope(|scope| {
+        let handles = fixtures
+            .chunks(group)
+            .map(|fixtures| {
+                scope.spawn(move || {
+                    fixtures
+                        .iter()
+                        .map(|fixture| {
+                            let mut sim = new_match(fixture)?;
+                            while sim.alive_count() > 1 && sim.tick() < season.max_ticks {
+                                sim.update(season.delta_time);
+                            }
+                            let order = finishing_order(&sim);
+                            Ok(order
+                                .iter()
+                                .filter_map(|seat| fixture.seats.iter().find(|(s, _)| s == seat))
+                                .map(|(_, entrant)| *entrant)
+                                .collect())
+                        })
+                        .collect::<Result<Vec<Vec<usize>>, String>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut orders = Vec::with_capacity(fixtures.len());
+        for handle in handles {
+            orders.extend(handle.join().expect("A match panicked")?);
+        }
+        Ok(orders)
+    })
+}
+
+/// Seats from first to last place: those still standing by the cells they hold, then the
+/// others from the last eliminated to the first.
+pub fn finishing_order(sim: &Simulation) -> Vec<i8> {
+    let mut standing = sim
+        .cannons
+        .iter()
+        .filter(|c| c.is_alive)
+        .map(|c| c.id)
+        .collect::<Vec<i8>>();
+    standing.sort_by_key(|id| std::cmp::Reverse(sim.grid.territory(*id)));
+    standing.extend(sim.eliminations().iter().rev().map(|e| e.victim));
+    standing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn rating(ladder: &Ladder, name: &str) -> f64 {
+        ladder.players[name].rating
+    }
+
+    #[test]
+    fn record_moves_winner_up_and_conserves_rating() {
+        let mut ladder = Ladder::default();
+        ladder.record(&names(&["a", "b", "c", "d"]));
+        let total = ["a", "b", "c", "d"]
+            .iter()
+            .map(|name| rating(&ladder, name))
+            .sum::<f64>();
+        assert!((total - 4.0 * Ladder::INITIAL_RATING).abs() < 1e-9);
+        assert!(rating(&ladder, "a") > rating(&ladder, "b"));
+        assert!(rating(&ladder, "b") > rating(&ladder, "c"));
+        assert!(rating(&ladder, "c") > rating(&ladder, "d"));
+        assert_eq!(ladder.players["a"].wins, 1);
+        assert_eq!(ladder.players["d"].wins, 0);
+    }
+
+    #[test]
+    fn record_of_two_equal_players_moves_half_of_k() {
+        let mut ladder = Ladder::default();
+        ladder.record(&names(&["a", "b"]));
+        assert!((rating(&ladder, "a") - (Ladder::INITIAL_RATING + Ladder::K / 2.0)).abs() < 1e-9);
+        assert!((rating(&ladder, "b") - (Ladder::INITIAL_RATING - Ladder::K / 2.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn combinations_choose_every_group_once() {
+        assert_eq!(combinations(5, 4).len(), 5);
+        assert_eq!(combinations(6, 4).len(), 15);
+        assert_eq!(combinations(4, 4), vec![vec![0, 1, 2, 3]]);
+        assert_eq!(combinations(3, 2), vec![vec![0, 1], vec![0, 2], vec![1, 2]]);
+    }
+
+    #[test]
+    fn schedule_seats_each_group_in_turn_through_the_arrangements() {
+        let season = Season {
+            rounds: 2,
+            seed: 1,
+            delta_time: 0.01,
+            max_ticks: 1,
+        };
+        let fixtures = schedule(&season, 5);
+        assert_eq!(fixtures.len(), 10);
+        assert_eq!(fixtures[0].seats, vec![(1, 0), (2, 1), (3, 2), (4, 3)]);
+        assert_eq!(fixtures[1].seats, vec![(2, 0), (4, 1), (1, 2), (3, 3)]);
+    }
+}
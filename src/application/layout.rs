@@ -0,0 +1,84 @@
+use graphics::math::Matrix2d;
+use graphics::Transformed;
+
+use super::common;
+
+// Everything is laid out and simulated in these logical units, then scaled to the window.
+pub const GRID_SIZE: i32 = common::CELL_WIDTH * common::CELL_EDGES * 2;
+pub const WIDTH: i32 = GRID_SIZE + (4 * common::BORDER_SIZE) + (2 * common::SIDE_WIDTH);
+pub const HEIGHT: i32 = GRID_SIZE + (2 * common::BORDER_SIZE);
+
+/// Playing field as [x, y, width, height].
+pub fn field_rect() -> [f64; 4] {
+    [
+        (2 * common::BORDER_SIZE + common::SIDE_WIDTH) as f64,
+        common::BORDER_SIZE as f64,
+        GRID_SIZE as f64,
+        GRID_SIZE as f64,
+    ]
+}
+
+/// Frame borders around the side panels and the playing field.
+pub fn frame_rects() -> [[f64; 4]; 6] {
+    let border = common::BORDER_SIZE as f64;
+    let side = common::SIDE_WIDTH as f64;
+    let grid = GRID_SIZE as f64;
+    let (width, height) = (WIDTH as f64, HEIGHT as f64);
+    [
+        [0.0, 0.0, border, height],
+        [border + side, 0.0, border, height],
+        [2.0 * border + side + grid, 0.0, border, height],
+        [width - border, 0.0, border, height],
+        [0.0, 0.0, width, border],
+        [0.0, border + grid, width, border],
+    ]
+}
+
+/// Top left corner of the plinko board for a player, left panel for 1 and 3, right for 2 and 4.
+pub fn plinko_position(id: i8) -> [f64; 2] {
+    let x = if id % 2 == 1 {
+        common::BORDER_SIZE
+    } else {
+        common::BORDER_SIZE * 3 + GRID_SIZE + common::SIDE_WIDTH
+    };
+    let y = if id <= 2 {
+        common::BORDER_SIZE
+    } else {
+        common::BORDER_SIZE + (common::CELL_EDGES * common::CELL_WIDTH)
+    };
+    [x as f64, y as f64]
+}
+
+/// Scales the logical layout into a window of any size, keeping its aspect ratio.
+#[derive(Clone, Copy)]
+pub struct View {
+    scale: f64,
+    offset: [f64; 2],
+}
+
+impl View {
+    pub fn fit(window_size: [f64; 2]) -> View {
+        let scale = (window_size[0] / WIDTH as f64).min(window_size[1] / HEIGHT as f64);
+        View {
+            scale,
+            offset: [
+                (window_size[0] - WIDTH as f64 * scale) / 2.0,
+                (window_size[1] - HEIGHT as f64 * scale) / 2.0,
+            ],
+        }
+    }
+
+    pub fn transform(&self, transform: Matrix2d) -> Matrix2d {
+        transform
+            .trans(self.offset[0], self.offset[1])
+            .scale(self.scale, self.scale)
+    }
+
+    /// Maps a window position, such as the mouse cursor, back into logical units.
+    pub fn to_logical(self, position: [f64; 2]) -> [f64; 2] {
+        [
+            (position[0] - self.offset[0]) / self.scale,
+            (position[1] - self.offset[1]) / self.scale,
+        ]
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+
+use super::simulation::SeatInput;
+use super::Simulation;
+
+/// Everything sent between the host and the other players, one JSON object per line.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    Welcome {
+        seat: i8,
+        seed: u64,
+        delta_time: f64,
+        aims: Vec<String>,
+        fire_controls: Vec<String>,
+    },
+    Input {
+        input: Option<SeatInput>,
+    },
+    Tick {
+        tick: u64,
+        inputs: Vec<Option<SeatInput>>,
+    },
+    Checksum {
+        tick: u64,
+        value: u64,
+    },
+    Desync {
+        tick: u64,
+        seat: i8,
+    },
+}
+
+/// A line read from a connection, or the news that it closed.
+enum Received {
+    Message(i8, Message),
+    Closed(i8),
+}
+
+struct Peer {
+    seat: i8,
+    stream: TcpStream,
+    is_connected: bool,
+    latest: Option<SeatInput>,
+}
+
+/// Keeps the simulations of players on different machines in step by sharing only their inputs.
+///
+/// The host decides which inputs apply to each tick and sends them to everyone, including
+/// itself, so every machine runs exactly the same ticks with the same inputs. A seat without a
+/// person at it, or whose person has not touched the controls yet, is played by its strategy.
+/// Every `CHECKSUM_TICKS` ticks the players send a hash of their simulation to the host, which
+/// reports any difference as a desync.
+pub struct Lockstep {
+    seat: i8,
+    delta_time: f64,
+    local: Option<SeatInput>,
+    sent: Option<SeatInput>,
+    // the host's connections to each player, or a player's connection to the host
+    peers: Vec<Peer>,
+    is_host: bool,
+    received: Receiver<Received>,
+    checksums: HashMap<u64, u64>,
+    connection: Option<String>,
+    desync: Option<String>,
+}
+
+impl Lockstep {
+    const CHECKSUM_TICKS: u64 = 120;
+
+    /// Waits until `players - 1` others have joined on `port`, then starts the match.
+    pub fn host(
+        port: u16,
+        players: usize,
+        sim: &Simulation,
+        delta_time: f64,
+    ) -> Result<Lockstep, String> {
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .map_err(|e| format!("Unable to listen on port {}: {}", port, e))?;
+        let (sender, received) = mpsc::channel();
+        let mut peers = Vec::new();
+        for seat in 2..=players as i8 {
+            println!("Waiting for player {} on port {}", seat, port);
+            let (mut stream, address) = listener
+                .accept()
+                .map_err(|e| format!("Unable to accept player {}: {}", seat, e))?;
+            stream.set_nodelay(true).map_err(|e| e.to_string())?;
+            println!("Player {} joined from {}", seat, address);
+            let welcome = Message::Welcome {
+                seat,
+                seed: sim.seed(),
+                delta_time,
+                aims: sim.aim_names().to_vec(),
+                fire_controls: sim.fire_control_names().to_vec(),
+            };
+            send(&mut stream, &welcome)?;
+            let reader = stream.try_clone().map_err(|e| e.to_string())?;
+            listen(seat, BufReader::new(reader), sender.clone());
+            peers.push(Peer {
+                seat,
+                stream,
+                is_connected: true,
+                latest: None,
+            });
+        }
+        Ok(Lockstep {
+            seat: 1,
+            delta_time,
+            local: None,
+            sent: None,
+            peers,
+            is_host: true,
+            received,
+            checksums: HashMap::new(),
+            connection: None,
+            desync: None,
+        })
+    }
+
+    /// Connects to a host and returns the match it is playing, set up the same way.
+    pub fn join(address: &str) -> Result<(Lockstep, Simulation), String> {
+        let stream = TcpStream::connect(address)
+            .map_err(|e| format!("Unable to connect to {}: {}", address, e))?;
+        stream.set_nodelay(true).map_err(|e| e.to_string())?;
+        // the same reader is handed on afterwards, as it may already hold the first ticks
+        let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+        let mut line = String::new();
+        reader
+            .read_line(&mut line)
+            .map_err(|e| format!("Unable to read from host: {}", e))?;
+        let (seat, seed, delta_time, aims, fire_controls) = match serde_json::from_str(&line) {
+            Ok(Message::Welcome {
+                seat,
+                seed,
+                delta_time,
+                aims,
+                fire_controls,
+            }) => (seat, seed, delta_time, aims, fire_controls),
+            _ => return Err(format!("Unexpected greeting from host: {}", line.trim())),
+        };
+
+        let mut sim = Simulation::new(seed);
+        for (i, (aim, fire_control)) in aims.iter().zip(&fire_controls).enumerate() {
+            let id = (i + 1) as i8;
+            if !aim.is_empty() {
+                sim.set_aim(id, aim)?;
+            }
+            if !fire_control.is_empty() {
+                sim.set_fire_control(id, fire_control)?;
+            }
+        }
+
+        let (sender, received) = mpsc::channel();
+        listen(0, reader, sender);
+        let lockstep = Lockstep {
+            seat,
+            delta_time,
+            local: None,
+            sent: None,
+            peers: vec![Peer {
+                seat: 0,
+                stream,
+                is_connected: true,
+                latest: None,
+            }],
+            is_host: false,
+            received,
+            checksums: HashMap::new(),
+            connection: None,
+            desync: None,
+        };
+        Ok((lockstep, sim))
+    }
+
+    /// The player this machine controls.
+    pub fn seat(&self) -> i8 {
+        self.seat
+    }
+
+    /// Input of the person at this machine, which takes over their seat from the strategy.
+    pub fn local_input(&mut self) -> &mut SeatInput {
+        self.local.get_or_insert_with(SeatInput::default)
+    }
+
+    /// A desync or connection problem worth showing to the people playing, the desync first
+    /// as it spoils the rest of the match.
+    pub fn status(&self) -> Option<&str> {
+        self.desync.as_deref().or(self.connection.as_deref())
+    }
+
+    /// Whether ticks can still arrive. The host keeps playing when everyone else has left.
+    pub fn is_connected(&self) -> bool {
+        self.is_host || self.peers.iter().any(|p| p.is_connected)
+    }
+
+    /// Runs the ticks that are ready, calling `on_tick` after each. The host runs `ticks` new
+    /// ticks, the other players run whatever ticks the host has sent them so far.
+    pub fn advance<F>(&mut self, sim: &mut Simulation, ticks: usize, mut on_tick: F)
+    where
+        F: FnMut(&Simulation),
+    {
+        if self.is_host {
+            self.receive(sim, &mut on_tick);
+            for _ in 0..ticks {
+                self.host_tick(sim);
+                on_tick(sim);
+            }
+        } else {
+            if self.local != self.sent {
+                let message = Message::Input { input: self.local };
+                self.broadcast(&message);
+                self.sent = self.local;
+                // a drop position only applies to one tick
+                if let Some(local) = &mut self.local {
+                    local.drop_x = None;
+                }
+            }
+            self.receive(sim, &mut on_tick);
+        }
+    }
+
+    fn host_tick(&mut self, sim: &mut Simulation) {
+        let mut inputs = vec![None; 4];
+        inputs[(self.seat - 1) as usize] = self.local;
+        if let Some(local) = &mut self.local {
+            local.drop_x = None;
+        }
+        for peer in &mut self.peers {
+            inputs[(peer.seat - 1) as usize] = peer.latest;
+            if let Some(latest) = &mut peer.latest {
+                latest.drop_x = None;
+            }
+        }
+        let message = Message::Tick {
+            tick: sim.tick() + 1,
+            inputs: inputs.clone(),
+        };
+        self.broadcast(&message);
+        self.run_tick(sim, &inputs);
+    }
+
+    fn run_tick(&mut self, sim: &mut Simulation, inputs: &[Option<SeatInput>]) {
+        for (i, input) in inputs.iter().enumerate() {
+            sim.apply_input((i + 1) as i8, input.as_ref());
+        }
+        sim.update(self.delta_time);
+
+        let tick = sim.tick();
+        if tick.is_multiple_of(Lockstep::CHECKSUM_TICKS) {
+            if self.is_host {
+                self.checksums.insert(tick, sim.checksum());
+                // players that fall far behind or drop out never report old ticks
+                let oldest = tick.saturating_sub(Lockstep::CHECKSUM_TICKS * 60);
+                self.checksums.retain(|t, _| *t >= oldest);
+            } else {
+                let message = Message::Checksum {
+                    tick,
+                    value: sim.checksum(),
+                };
+                self.broadcast(&message);
+            }
+        }
+    }
+
+    fn receive<F>(&mut self, sim: &mut Simulation, on_tick: &mut F)
+    where
+        F: FnMut(&Simulation),
+    {
+        while let Ok(received) = self.received.try_recv() {
+            match received {
+                Received::Message(seat, message) => {
+                    let tick = sim.tick();
+                    self.handle(sim, seat, message);
+                    if sim.tick() != tick {
+                        on_tick(sim);
+                    }
+                }
+                Received::Closed(seat) => {
+                    if let Some(peer) = self.peers.iter_mut().find(|p| p.seat == seat) {
+                        peer.is_connected = false;
+                        peer.latest = None;
+                    }
+                    self.connection = Some(if self.is_host {
+                        format!("player {} left, a strategy plays for them", seat)
+                    } else {
+                        "lost connection to the host".to_string()
+                    });
+                }
+            }
+        }
+    }
+
+    fn handle(&mut self, sim: &mut Simulation, seat: i8, message: Message) {
+        match message {
+            Message::Input { input } if self.is_host => {
+                if let Some(peer) = self.peers.iter_mut().find(|p| p.seat == seat) {
+                    // keep a drop that has not been applied yet
+                    let drop_x = peer.latest.and_then(|l| l.drop_x);
+                    peer.latest = input.map(|i| SeatInput {
+                        drop_x: i.drop_x.or(drop_x),
+                        ..i
+                    });
+                }
+            }
+            Message::Tick { tick, inputs } if !self.is_host && tick == sim.tick() + 1 => {
+                self.run_tick(sim, &inputs);
+            }
+            Message::Checksum { tick, value } if self.is_host && self.differs(tick, value) => {
+                self.report_desync(tick, seat);
+                let message = Message::Desync { tick, seat };
+                self.broadcast(&message);
+            }
+            Message::Desync { tick, seat } if !self.is_host => {
+                self.report_desync(tick, seat);
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether a player's checksum differs from the host's for the same tick.
+    fn differs(&self, tick: u64, value: u64) -> bool {
+        self.checksums
+            .get(&tick)
+            .is_some_and(|expected| *expected != value)
+    }
+
+    fn report_desync(&mut self, tick: u64, seat: i8) {
+        // only the first one matters, everything after it differs as well
+        if self.desync.is_none() {
+            self.desync = Some(format!("DESYNC: player {} differs at tick {}", seat, tick));
+        }
+    }
+
+    fn broadcast(&mut self, message: &Message) {
+        for peer in self.peers.iter_mut().filter(|p| p.is_connected) {
+            if send(&mut peer.stream, message).is_err() {
+                // the reading thread reports the closed connection
+                peer.is_connected = false;
+            }
+        }
+    }
+}
+
+fn send(stream: &mut TcpStream, message: &Message) -> Result<(), String> {
+    let mut line = serde_json::to_string(message).map_err(|e| e.to_string())?;
+    line.push('\n');
+    stream
+        .write_all(line.as_bytes())
+        .map_err(|e| format!("Unable to send to peer: {}", e))
+}
+
+/// Reads messages from a connection on its own thread, so the game loop never waits on it.
+fn listen(seat: i8, reader: BufReader<TcpStream>, sender: Sender<Received>) {
+    thread::spawn(move || {
+        for line in reader.lines() {
+            let message = match line.map(|l| serde_json::from_str::<Message>(&l)) {
+                Ok(Ok(message)) => message,
+                _ => break,
+            };
+            if sender.send(Received::Message(seat, message)).is_err() {
+                return;
+            }
+        }
+        let _ = sender.send(Received::Closed(seat));
+    });
+}
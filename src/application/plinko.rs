@@ -1,9 +1,13 @@
 use graphics::{Text, Transformed};
 use opengl_graphics::{GlGraphics, GlyphCache};
 use rand::Rng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
 
 mod puck;
 
+use super::arena::PlinkoGeometry;
+
 use puck::Puck;
 
 pub struct PlinkoEvent {
@@ -17,6 +21,7 @@ impl PlinkoEvent {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Plinko {
     id: i8,
     position: [f64; 2],
@@ -26,41 +31,66 @@ pub struct Plinko {
     time: f64,
     well_x: f64,
     shot_count: i32,
+    next_drop_x: Option<f64>,
+    // the multiplier well is on the left and grows to the right
+    is_mirrored: bool,
     pub is_alive: bool,
 }
 
 impl Plinko {
     const BOUNDARY_WIDTH: f64 = 10.0;
     const WELL_DIVIDER_WIDTH: f64 = 20.0;
-    const MIN_WELL_WIDTH: f64 = 100.0;
+    pub const MIN_WELL_WIDTH: f64 = 100.0;
     const NEW_PUCK_TIME: f64 = 80.0;
     const WELL_DEPTH: f64 = 20.0;
     const MAX_PUCKS: usize = 6;
     const SCORE_SIZE: u32 = 42;
     const WELL_WIDTH_INCREMENT: f64 = 0.15;
 
-    pub fn new(id: i8, color: &str, position: [f64; 2]) -> Plinko {
+    /// Width the multiplier well grows to at most, as wide as the board leaves room for.
+    pub fn max_well_width() -> f64 {
+        super::common::SIDE_WIDTH as f64 - Plinko::BOUNDARY_WIDTH * 2.0 - Plinko::MIN_WELL_WIDTH
+    }
+
+    pub fn new(
+        id: i8,
+        color: &str,
+        position: [f64; 2],
+        geometry: &PlinkoGeometry,
+        is_mirrored: bool,
+    ) -> Plinko {
         // stationary pucks are "pins" to bounce off of
         let mut pins = Vec::new();
-        const HORZ: i32 = 3;
-        const HSPACE: f64 =
-            ((super::common::SIDE_WIDTH - (Plinko::BOUNDARY_WIDTH * 2.0) as i32) / HORZ) as f64;
-        const VERT: i32 = 5;
-        const VSPACE: f64 = ((super::common::CELL_EDGES * super::common::CELL_WIDTH) / VERT) as f64;
-        for v in 1..VERT {
-            let mut max = HORZ + 1;
+        let horz = geometry.pin_columns;
+        let hspace =
+            ((super::common::SIDE_WIDTH - (Plinko::BOUNDARY_WIDTH * 2.0) as i32) / horz) as f64;
+        let vert = geometry.pin_rows;
+        let vspace = ((super::common::CELL_EDGES * super::common::CELL_WIDTH) / vert) as f64;
+        for v in 1..vert {
+            let mut max = horz + 1;
             let mut offset = 0.0;
             if v % 2 == 0 {
                 max = max - 1;
-                offset = HSPACE / 2.0;
+                offset = hspace / 2.0;
             }
             for h in 0..max {
+                let mut x = Plinko::BOUNDARY_WIDTH + (hspace * h as f64) + offset;
+                if is_mirrored {
+                    x = super::common::SIDE_WIDTH as f64 - x;
+                }
                 pins.push(Puck::new_fixed([
-                    position[0] + Plinko::BOUNDARY_WIDTH + (HSPACE * h as f64) + offset,
-                    position[1] + (VSPACE * v as f64) - Puck::RADIUS,
+                    position[0] + x,
+                    position[1] + (vspace * v as f64) - Puck::RADIUS,
                 ]));
             }
         }
+        let well_x = if is_mirrored {
+            position[0] + Plinko::BOUNDARY_WIDTH + geometry.well_width
+        } else {
+            position[0] + super::common::SIDE_WIDTH as f64
+                - Plinko::BOUNDARY_WIDTH
+                - geometry.well_width
+        };
 
         Plinko {
             id: id,
@@ -69,15 +99,15 @@ impl Plinko {
             pins: pins,
             pucks: Vec::new(),
             time: 0.0,
-            well_x: position[0] + super::common::SIDE_WIDTH as f64
-                - Plinko::BOUNDARY_WIDTH
-                - Plinko::MIN_WELL_WIDTH,
+            well_x,
             shot_count: 1,
+            next_drop_x: None,
+            is_mirrored,
             is_alive: true,
         }
     }
 
-    pub fn update<F>(&mut self, delta_time: f64, event_callback: F)
+    pub fn update<F>(&mut self, delta_time: f64, rng: &mut ChaCha12Rng, event_callback: F)
     where
         F: FnMut(PlinkoEvent),
     {
@@ -89,18 +119,20 @@ impl Plinko {
             if (self.time / Plinko::NEW_PUCK_TIME) as usize >= num_pucks
                 && num_pucks < Plinko::MAX_PUCKS
             {
-                let mut rng = rand::thread_rng();
-                let random_x = rng.gen_range(
-                    0.0..(super::common::SIDE_WIDTH as f64
-                        - (Puck::RADIUS * 2.0)
-                        - (Plinko::BOUNDARY_WIDTH * 2.0)),
-                );
+                // a chosen drop falls straight down, otherwise the puck starts anywhere
+                let (drop_x, angle) = match self.next_drop_x.take() {
+                    Some(x) => (x, 90.0),
+                    None => (
+                        rng.gen_range(0.0..Plinko::drop_range()),
+                        rng.gen_range(10.0..170.0),
+                    ),
+                };
                 self.pucks.push(Puck::new_active(
                     [
-                        self.position[0] + Puck::RADIUS + Plinko::BOUNDARY_WIDTH + random_x,
+                        self.position[0] + Puck::RADIUS + Plinko::BOUNDARY_WIDTH + drop_x,
                         self.position[1] + Plinko::BOUNDARY_WIDTH + (3.0 * Puck::RADIUS / 2.0),
                     ],
-                    rng.gen_range(10.0..170.0),
+                    angle,
                     self.color,
                 ));
             }
@@ -119,6 +151,45 @@ impl Plinko {
         }
     }
 
+    fn drop_range() -> f64 {
+        super::common::SIDE_WIDTH as f64 - (Puck::RADIUS * 2.0) - (Plinko::BOUNDARY_WIDTH * 2.0)
+    }
+
+    /// Band along the top of the board where clicking chooses the next drop position.
+    pub fn get_drop_rect(&self) -> [f64; 4] {
+        [
+            self.position[0] + Plinko::BOUNDARY_WIDTH,
+            self.position[1] + Plinko::BOUNDARY_WIDTH,
+            super::common::SIDE_WIDTH as f64 - Plinko::BOUNDARY_WIDTH * 2.0,
+            Puck::RADIUS * 3.0,
+        ]
+    }
+
+    /// Drops the next puck straight down from `x` instead of a random position.
+    pub fn choose_drop(&mut self, x: f64) {
+        let offset = x - self.position[0] - Puck::RADIUS - Plinko::BOUNDARY_WIDTH;
+        self.next_drop_x = Some(offset.clamp(0.0, Plinko::drop_range()));
+    }
+
+    /// Changes the direction of every falling puck by `degrees`.
+    pub fn nudge(&mut self, degrees: f64) {
+        for puck in &mut self.pucks {
+            puck.nudge(degrees);
+        }
+    }
+
+    pub fn id(&self) -> i8 {
+        self.id
+    }
+
+    pub fn shot_count(&self) -> i32 {
+        self.shot_count
+    }
+
+    pub fn pucks(&self) -> &[Puck] {
+        &self.pucks
+    }
+
     fn get_min_max(&self) -> [f64; 4] {
         return [
             self.position[0],
@@ -128,27 +199,35 @@ impl Plinko {
         ];
     }
 
-    fn get_fire_rect(&self) -> [f64; 4] {
-        let [xmin, _xmax, _ymin, ymax] = self.get_min_max();
-        return [
-            xmin,
-            ymax - Plinko::WELL_DEPTH / 2.0 - Plinko::BOUNDARY_WIDTH,
-            self.well_x - xmin,
-            Plinko::WELL_DEPTH / 2.0,
-        ];
+    /// The wells on either side of the divider as [x, y, width, height], left one first.
+    fn well_rects(&self) -> [[f64; 4]; 2] {
+        let [xmin, xmax, _ymin, ymax] = self.get_min_max();
+        let y = ymax - Plinko::WELL_DEPTH / 2.0 - Plinko::BOUNDARY_WIDTH;
+        [
+            [xmin, y, self.well_x - xmin, Plinko::WELL_DEPTH / 2.0],
+            [self.well_x, y, xmax - self.well_x, Plinko::WELL_DEPTH / 2.0],
+        ]
     }
 
-    fn get_multi_rect(&self) -> [f64; 4] {
-        let [_xmin, xmax, _ymin, ymax] = self.get_min_max();
-        return [
-            self.well_x,
-            ymax - Plinko::WELL_DEPTH / 2.0 - Plinko::BOUNDARY_WIDTH,
-            xmax - self.well_x,
-            Plinko::WELL_DEPTH / 2.0,
-        ];
+    pub fn get_fire_rect(&self) -> [f64; 4] {
+        let [left, right] = self.well_rects();
+        if self.is_mirrored {
+            right
+        } else {
+            left
+        }
     }
 
-    fn get_divider_rect(&self) -> [f64; 4] {
+    pub fn get_multi_rect(&self) -> [f64; 4] {
+        let [left, right] = self.well_rects();
+        if self.is_mirrored {
+            left
+        } else {
+            right
+        }
+    }
+
+    pub fn get_divider_rect(&self) -> [f64; 4] {
         let [_xmin, _xmax, _ymin, ymax] = self.get_min_max();
         return [
             self.well_x - Plinko::WELL_DIVIDER_WIDTH / 2.0,
@@ -177,6 +256,12 @@ impl Plinko {
             pin.draw(&c, gl);
         }
 
+        if let Some(drop_x) = self.next_drop_x {
+            let x = self.position[0] + Puck::RADIUS + Plinko::BOUNDARY_WIDTH + drop_x;
+            let [_, y, _, height] = self.get_drop_rect();
+            graphics::line(self.color, 1.0, [x, y, x, y + height], c.transform, gl);
+        }
+
         graphics::rectangle(
             graphics::color::hex(super::colors::FIRE_WELL),
             self.get_fire_rect(),
@@ -258,9 +343,18 @@ impl Plinko {
                 self.shot_count *= 2;
 
                 // gradually make multiplier well bigger
-                let new_well_x = self.well_x - Plinko::WELL_WIDTH_INCREMENT;
-                let min_well_x = self.position[0] + Plinko::BOUNDARY_WIDTH + Plinko::MIN_WELL_WIDTH;
-                self.well_x = new_well_x.max(min_well_x);
+                if self.is_mirrored {
+                    let new_well_x = self.well_x + Plinko::WELL_WIDTH_INCREMENT;
+                    let max_well_x = self.position[0] + super::common::SIDE_WIDTH as f64
+                        - Plinko::BOUNDARY_WIDTH
+                        - Plinko::MIN_WELL_WIDTH;
+                    self.well_x = new_well_x.min(max_well_x);
+                } else {
+                    let new_well_x = self.well_x - Plinko::WELL_WIDTH_INCREMENT;
+                    let min_well_x =
+                        self.position[0] + Plinko::BOUNDARY_WIDTH + Plinko::MIN_WELL_WIDTH;
+                    self.well_x = new_well_x.max(min_well_x);
+                }
 
                 puck.is_alive = false;
             } else if puck.collides_with(fire_rect) {
@@ -1,6 +1,8 @@
 use graphics::Radians;
 use opengl_graphics::GlGraphics;
+use serde::{Deserialize, Serialize};
 
+#[derive(Serialize, Deserialize)]
 pub struct Puck {
     position: [f64; 2],
     angle: f64,
@@ -44,6 +46,25 @@ impl Puck {
         graphics::ellipse(self.color, rect, c.transform, gl);
     }
 
+    pub fn position(&self) -> [f64; 2] {
+        self.position
+    }
+
+    /// Distance moved per tick as [dx, dy].
+    pub fn velocity(&self) -> [f64; 2] {
+        [
+            self.speed * self.angle.deg_to_rad().cos(),
+            self.speed * self.angle.deg_to_rad().sin(),
+        ]
+    }
+
+    /// Turns a moving puck by `degrees`, leaving fixed pegs alone.
+    pub fn nudge(&mut self, degrees: f64) {
+        if self.speed > 0.0 {
+            self.angle += degrees;
+        }
+    }
+
     pub fn step(&mut self, boundaries: [f64; 4]) {
         if self.speed > 0.0 {
             self.check_boundary_collisions(boundaries);
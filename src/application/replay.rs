@@ -0,0 +1,359 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use opengl_graphics::GlGraphics;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+use super::colors;
+use super::highlights::{Moment, Watch};
+use super::simulation::RecordedTick;
+use super::timeline;
+use super::Simulation;
+
+/// A line of a recording.
+#[derive(Serialize)]
+enum SavedEntry<'a> {
+    Keyframe {
+        delta_time: f64,
+        snapshot: &'a RawValue,
+    },
+    Tick(&'a RecordedTick),
+}
+
+#[derive(Deserialize)]
+enum Entry {
+    Keyframe {
+        delta_time: f64,
+        snapshot: Box<RawValue>,
+    },
+    Tick(RecordedTick),
+}
+
+/// Writes a match to a file while it is played, to be watched again with `Replay`.
+///
+/// A recording has a line of JSON for every tick with what the players did, and every few
+/// seconds a snapshot of the whole match, a keyframe to start watching from. Whenever the
+/// match is started afresh, loaded or rewound, the recording carries on from a new keyframe.
+pub struct Recorder {
+    path: String,
+    file: BufWriter<File>,
+    // tick of the match at the last keyframe
+    keyframe_tick: u64,
+}
+
+impl Recorder {
+    const KEYFRAME_TICKS: u64 = 600;
+
+    pub fn create(path: &str) -> Result<Recorder, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("Unable to write recording '{}': {}", path, e))?;
+        Ok(Recorder {
+            path: path.to_string(),
+            file: BufWriter::new(file),
+            keyframe_tick: 0,
+        })
+    }
+
+    /// Writes the ticks played since the last call, which should follow every update.
+    pub fn record(&mut self, sim: &mut Simulation, delta_time: f64) -> Result<(), String> {
+        if !sim.is_journaling() {
+            // the first match, or one that took the place of the last
+            sim.start_journal();
+            return self.write_keyframe(sim, delta_time);
+        }
+        for tick in sim.take_journal() {
+            self.write(&SavedEntry::Tick(&tick))?;
+        }
+        if sim.tick() >= self.keyframe_tick + Recorder::KEYFRAME_TICKS {
+            self.write_keyframe(sim, delta_time)?;
+        }
+        Ok(())
+    }
+
+    fn write_keyframe(&mut self, sim: &Simulation, delta_time: f64) -> Result<(), String> {
+        let snapshot = RawValue::from_string(sim.snapshot()).expect("A snapshot is JSON");
+        self.write(&SavedEntry::Keyframe {
+            delta_time,
+            snapshot: &snapshot,
+        })?;
+        self.keyframe_tick = sim.tick();
+        // a recording that is cut short still plays up to here
+        self.file
+            .flush()
+            .map_err(|e| format!("Unable to write recording '{}': {}", self.path, e))
+    }
+
+    fn write(&mut self, entry: &SavedEntry) -> Result<(), String> {
+        serde_json::to_writer(&mut self.file, entry)
+            .map_err(|e| e.to_string())
+            .and_then(|()| writeln!(self.file).map_err(|e| e.to_string()))
+            .map_err(|e| format!("Unable to write recording '{}': {}", self.path, e))
+    }
+}
+
+struct Keyframe {
+    // ticks into the recording
+    position: usize,
+    delta_time: f64,
+    snapshot: String,
+}
+
+impl Keyframe {
+    fn view(&self) -> Simulation {
+        Simulation::view_snapshot(&self.snapshot).expect("Keyframes are read on opening")
+    }
+}
+
+/// A recorded match being watched, see `Recorder`.
+///
+/// The match is played again from the keyframes with the recorded decisions of each tick,
+/// so seeking starts from the nearest keyframe before the wanted tick instead of the start.
+pub struct Replay {
+    ticks: Vec<RecordedTick>,
+    // in order of position, where a later keyframe at the same position replaces the match
+    keyframes: Vec<Keyframe>,
+    bookmarks: Vec<Moment>,
+    // ticks played to reach the match on show
+    position: usize,
+    delta_time: f64,
+}
+
+impl Replay {
+    // jumping to a bookmark shows a little of what led up to it
+    const LEAD_IN_TICKS: usize = 2 * timeline::TICKS_PER_SECOND as usize;
+    // how long the label of a bookmark stays up once it has been played
+    const LABEL_TICKS: usize = 3 * timeline::TICKS_PER_SECOND as usize;
+
+    /// Reads a recording and plays it through once to find its bookmarks, returning it with
+    /// the match at its start.
+    pub fn open(path: &str) -> Result<(Replay, Simulation), String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read recording '{}': {}", path, e))?;
+        let mut ticks = Vec::new();
+        let mut keyframes = Vec::new();
+        // the last line of a recording that was cut short may be unfinished
+        let lines = text.split_terminator('\n').take(text.matches('\n').count());
+        for (number, line) in lines.enumerate() {
+            let entry = serde_json::from_str(line)
+                .map_err(|e| format!("Invalid recording '{}' line {}: {}", path, number + 1, e))?;
+            match entry {
+                Entry::Keyframe {
+                    delta_time,
+                    snapshot,
+                } => {
+                    let snapshot = snapshot.get().to_string();
+                    Simulation::view_snapshot(&snapshot).map_err(|e| {
+                        format!("{} in recording '{}' line {}", e, path, number + 1)
+                    })?;
+                    keyframes.push(Keyframe {
+                        position: ticks.len(),
+                        delta_time,
+                        snapshot,
+                    });
+                }
+                Entry::Tick(tick) => ticks.push(tick),
+            }
+        }
+        if keyframes.first().is_none_or(|k| k.position != 0) {
+            return Err(format!(
+                "Recording '{}' doesn't start with a keyframe",
+                path
+            ));
+        }
+        let mut replay = Replay {
+            ticks,
+            keyframes,
+            bookmarks: Vec::new(),
+            position: 0,
+            delta_time: 0.0,
+        };
+        replay.bookmarks = replay.find_bookmarks();
+        let sim = replay.seek_to(0);
+        Ok((replay, sim))
+    }
+
+    /// Plays the whole recording, noting its bookmarks and any keyframe it disagrees with,
+    /// which would mean the recording was made by a different version of the game.
+    fn find_bookmarks(&mut self) -> Vec<Moment> {
+        let mut bookmarks = Vec::new();
+        let mut sim = self.seek_to(0);
+        let mut watch = Watch::new(&sim);
+        while self.position < self.ticks.len() {
+            sim.replay(self.delta_time, &self.ticks[self.position]);
+            self.position += 1;
+            watch.observe(&sim, self.position, &mut bookmarks);
+            if let Some(keyframe) = self.keyframe_at(self.position) {
+                let next = keyframe.view();
+                self.delta_time = keyframe.delta_time;
+                if next.tick() != sim.tick() {
+                    // the match was started afresh, loaded or rewound while recording
+                    watch.finish(&sim, &mut bookmarks);
+                    watch = Watch::new(&next);
+                } else if next.checksum() != sim.checksum() {
+                    eprintln!(
+                        "The recording played out differently than it was recorded at tick {}",
+                        sim.tick()
+                    );
+                }
+                sim = next;
+            }
+        }
+        watch.finish(&sim, &mut bookmarks);
+        // close calls are only added once the cannon has survived them
+        bookmarks.sort_by_key(|b| b.position);
+        bookmarks
+    }
+
+    /// Kills, big swings in territory, close calls and plinko jackpots, in the order they were
+    /// played.
+    pub fn bookmarks(&self) -> &[Moment] {
+        &self.bookmarks
+    }
+
+    /// The last keyframe at exactly `position`.
+    fn keyframe_at(&self, position: usize) -> Option<&Keyframe> {
+        self.keyframes.iter().rev().find(|k| k.position == position)
+    }
+
+    /// Plays the next tick, unless the recording is over.
+    pub fn advance(&mut self, sim: &mut Simulation) {
+        let Some(tick) = self.ticks.get(self.position) else {
+            return;
+        };
+        sim.replay(self.delta_time, tick);
+        self.position += 1;
+        if let Some(keyframe) = self.keyframe_at(self.position) {
+            *sim = keyframe.view();
+            self.delta_time = keyframe.delta_time;
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.position == self.ticks.len()
+    }
+
+    /// Ticks played to reach the match on show.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Ticks in the whole recording.
+    pub fn end(&self) -> usize {
+        self.ticks.len()
+    }
+
+    /// The match `position` ticks into the recording, played from the keyframe before it.
+    pub fn seek_to(&mut self, position: usize) -> Simulation {
+        let position = position.min(self.ticks.len());
+        let index = self.keyframes.partition_point(|k| k.position <= position) - 1;
+        let keyframe = &self.keyframes[index];
+        let mut sim = keyframe.view();
+        self.delta_time = keyframe.delta_time;
+        self.position = keyframe.position;
+        while self.position < position {
+            self.advance(&mut sim);
+        }
+        sim
+    }
+
+    /// The match at a point of the timeline bar, in logical units.
+    pub fn seek(&mut self, x: f64) -> Simulation {
+        let share = timeline::share_at(x);
+        self.seek_to((share * self.ticks.len() as f64).round() as usize)
+    }
+
+    /// Jumps to a little before the next bookmark, or the previous one when not `forward`.
+    pub fn jump_to_bookmark(&mut self, forward: bool) -> Option<Simulation> {
+        let starts = self
+            .bookmarks
+            .iter()
+            .map(|b| b.position.saturating_sub(Replay::LEAD_IN_TICKS));
+        let start = if forward {
+            starts.filter(|start| *start > self.position).min()
+        } else {
+            starts.filter(|start| *start < self.position).max()
+        };
+        start.map(|start| self.seek_to(start))
+    }
+
+    /// Where the recording is, and the bookmark that was just played if any.
+    pub fn status(&self) -> String {
+        let seconds = |ticks: usize| ticks as f64 / timeline::TICKS_PER_SECOND as f64;
+        let mut status = format!(
+            "REPLAY {:.1}s / {:.1}s",
+            seconds(self.position),
+            seconds(self.ticks.len())
+        );
+        let recent = self.bookmarks.iter().rev().find(|b| {
+            b.position <= self.position && self.position - b.position < Replay::LABEL_TICKS
+        });
+        if let Some(bookmark) = recent {
+            status += &format!(": {}", bookmark.label);
+        }
+        status
+    }
+
+    /// Draws the timeline, filled up to the tick on show, with a mark at every bookmark.
+    pub fn draw(&self, c: &graphics::Context, gl: &mut GlGraphics) {
+        let length = self.ticks.len().max(1) as f64;
+        timeline::draw(self.position as f64 / length, c, gl);
+        for bookmark in &self.bookmarks {
+            timeline::draw_mark(
+                bookmark.position as f64 / length,
+                colors::cannon(bookmark.player),
+                c,
+                gl,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELTA_TIME: f64 = 1.0 / 120.0;
+
+    #[test]
+    fn replay_plays_out_as_recorded() {
+        let path = std::env::temp_dir().join(format!("replay-test-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut sim = Simulation::new(11);
+        sim.set_aim(2, "nearest").unwrap();
+        let mut recorder = Recorder::create(path).unwrap();
+        recorder.record(&mut sim, DELTA_TIME).unwrap();
+        // frames of a few ticks, with something done to the match between some of them
+        for frame in 0..400u32 {
+            let id = (frame % 4 + 1) as i8;
+            match frame % 7 {
+                0 => sim.load_cannon(id, 3),
+                2 => {
+                    let x = sim.plinkos[(id - 1) as usize].get_drop_rect()[0] + frame as f64 % 90.0;
+                    sim.choose_drop(id, x);
+                }
+                4 => sim.nudge_plinko(id, 15.0),
+                _ => {}
+            }
+            for _ in 0..(frame % 3 + 1) {
+                sim.update(DELTA_TIME);
+            }
+            recorder.record(&mut sim, DELTA_TIME).unwrap();
+        }
+        drop(recorder);
+
+        let opened = Replay::open(path);
+        std::fs::remove_file(path).unwrap();
+        let (replay, mut played) = opened.unwrap();
+        assert!(replay.keyframes.len() >= 2);
+        assert!(replay.ticks.iter().filter(|t| !t.inputs.is_empty()).count() > 100);
+        for (position, tick) in replay.ticks.iter().enumerate() {
+            played.replay(DELTA_TIME, tick);
+            if let Some(keyframe) = replay.keyframe_at(position + 1) {
+                assert_eq!(played.checksum(), keyframe.view().checksum());
+            }
+        }
+        assert_eq!(played.tick(), sim.tick());
+        assert_eq!(played.checksum(), sim.checksum());
+    }
+}
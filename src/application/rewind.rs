@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+
+use opengl_graphics::GlGraphics;
+
+use super::timeline;
+use super::Simulation;
+
+/// Snapshots of the last seconds of play, to be scrubbed through and played on from.
+pub struct Rewind {
+    // tick and snapshot, oldest first
+    frames: VecDeque<(u64, String)>,
+    capacity: usize,
+    // the frame on show while rewinding
+    cursor: Option<usize>,
+}
+
+impl Rewind {
+    pub const DEFAULT_SECONDS: f64 = 10.0;
+    pub const FRAMES_PER_SECOND: u64 = 10;
+    const INTERVAL: u64 = timeline::TICKS_PER_SECOND / Rewind::FRAMES_PER_SECOND;
+
+    /// Keeps the last `seconds` of play, or nothing when it is 0.
+    pub fn new(seconds: f64) -> Rewind {
+        let capacity = (seconds * Rewind::FRAMES_PER_SECOND as f64) as usize;
+        Rewind {
+            frames: VecDeque::with_capacity(capacity + 1),
+            capacity,
+            cursor: None,
+        }
+    }
+
+    pub fn is_rewinding(&self) -> bool {
+        self.cursor.is_some()
+    }
+
+    /// Keeps a frame of the match when enough of it has been played since the last one, and
+    /// starts over when the match went back in time, e.g. after a restart.
+    pub fn record(&mut self, sim: &Simulation) {
+        if self.capacity == 0 || self.is_rewinding() {
+            return;
+        }
+        match self.frames.back() {
+            Some((tick, _)) if sim.tick() < *tick => self.frames.clear(),
+            Some((tick, _)) if sim.tick() < tick + Rewind::INTERVAL => return,
+            _ => {}
+        }
+        self.frames.push_back((sim.tick(), sim.snapshot()));
+        while self.frames.len() > self.capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.cursor = None;
+    }
+
+    /// Starts rewinding from the current state of `sim`, returning the match to show, or
+    /// `None` when there is nothing to go back to.
+    pub fn start(&mut self, sim: &Simulation) -> Option<Simulation> {
+        if self.capacity == 0 {
+            return None;
+        }
+        // the present is the last frame, so that rewinding can go back to where it started
+        if self
+            .frames
+            .back()
+            .is_none_or(|(tick, _)| *tick != sim.tick())
+        {
+            self.frames.push_back((sim.tick(), sim.snapshot()));
+        }
+        self.show(self.frames.len() - 1)
+    }
+
+    /// Moves the frame on show by `frames`, back when negative.
+    pub fn step(&mut self, frames: isize) -> Option<Simulation> {
+        let cursor = self.cursor? as isize + frames;
+        self.show(cursor.clamp(0, self.frames.len() as isize - 1) as usize)
+    }
+
+    /// Moves to the frame under a point of the timeline bar, in logical units.
+    pub fn seek(&mut self, x: f64) -> Option<Simulation> {
+        self.cursor?;
+        let share = timeline::share_at(x);
+        self.show((share * (self.frames.len() - 1) as f64).round() as usize)
+    }
+
+    /// Whether a point in logical units is on the timeline bar, or near enough to grab it.
+    pub fn is_on_bar(&self, position: [f64; 2]) -> bool {
+        self.is_rewinding() && timeline::is_on_bar(position)
+    }
+
+    /// Stops rewinding and plays on from the frame on show, forgetting the frames after it.
+    pub fn resume(&mut self) -> Option<Result<Simulation, String>> {
+        let cursor = self.cursor.take()?;
+        self.frames.truncate(cursor + 1);
+        let (_, snapshot) = self.frames.back()?;
+        Some(Simulation::from_snapshot(snapshot))
+    }
+
+    fn show(&mut self, cursor: usize) -> Option<Simulation> {
+        let (_, snapshot) = self.frames.get(cursor)?;
+        match Simulation::view_snapshot(snapshot) {
+            Ok(sim) => {
+                self.cursor = Some(cursor);
+                Some(sim)
+            }
+            Err(message) => {
+                eprintln!("{}", message);
+                None
+            }
+        }
+    }
+
+    /// Seconds of play between the frame on show and the present.
+    pub fn seconds_back(&self) -> f64 {
+        match (
+            self.cursor.and_then(|c| self.frames.get(c)),
+            self.frames.back(),
+        ) {
+            (Some((shown, _)), Some((latest, _))) => {
+                (latest - shown) as f64 / timeline::TICKS_PER_SECOND as f64
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Draws the timeline of the kept frames while rewinding, filled up to the frame on show.
+    pub fn draw(&self, c: &graphics::Context, gl: &mut GlGraphics) {
+        let Some(cursor) = self.cursor else { return };
+        let share = cursor as f64 / (self.frames.len() - 1).max(1) as f64;
+        timeline::draw(share, c, gl);
+    }
+}
@@ -0,0 +1,39 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::RgbaImage;
+
+/// Saves the current contents of the window to `screenshot-<seconds>.png` and returns the path.
+pub fn save(draw_size: [u32; 2]) -> Result<String, String> {
+    let [width, height] = draw_size;
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(
+            0,
+            0,
+            width as i32,
+            height as i32,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut _,
+        );
+    }
+
+    // OpenGL rows start at the bottom of the window
+    let row = (width * 4) as usize;
+    let flipped = pixels
+        .chunks(row)
+        .rev()
+        .flatten()
+        .copied()
+        .collect::<Vec<u8>>();
+    let image = RgbaImage::from_raw(width, height, flipped).ok_or("Unexpected screenshot size")?;
+
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let path = format!("screenshot-{}.png", seconds);
+    image.save(&path).map_err(|e| e.to_string())?;
+    Ok(path)
+}
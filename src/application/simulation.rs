@@ -0,0 +1,442 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use super::arena::Arena;
+use super::bullet::Bullet;
+use super::cannon::aim::{self, ArenaView, CannonView};
+use super::cannon::bot;
+use super::cannon::fire;
+use super::cannon::script;
+use super::cannon::{Cannon, CannonParams, ManualControl};
+use super::colors;
+use super::grid::Grid;
+use super::layout;
+use super::plinko::{Plinko, PlinkoEvent};
+
+mod journal;
+mod snapshot;
+
+pub use journal::RecordedTick;
+use journal::{Input, Journal};
+
+/// What a person at one seat does during a tick: the held controls of their cannon and, for
+/// one tick, where their next plinko puck should drop.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SeatInput {
+    pub manual: ManualControl,
+    pub drop_x: Option<f64>,
+}
+
+/// A cannon destroyed by a bullet of another.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Elimination {
+    pub victim: i8,
+    pub killer: i8,
+}
+
+/// All of the game state that is advanced by `update`, independent of how it is drawn.
+///
+/// Every random choice comes from a generator seeded at the start of the match, so the same
+/// seed, setup and inputs always play out the same way.
+#[derive(Serialize, Deserialize)]
+pub struct Simulation {
+    pub grid: Grid,
+    pub cannons: [Cannon; 4],
+    pub bullets: Vec<Bullet>,
+    pub plinkos: [Plinko; 4],
+    #[serde(skip, default = "layout::field_rect")]
+    field_rect: [f64; 4],
+    arena: Arena,
+    // the image the grid was painted in, see `set_grid_image`
+    #[serde(default)]
+    grid_image: Option<String>,
+    seed: u64,
+    // the generator behind `StdRng`, which unlike it can be saved in a snapshot
+    rng: ChaCha12Rng,
+    tick: u64,
+    aim_names: [String; 4],
+    fire_control_names: [String; 4],
+    bot_commands: [Option<String>; 4],
+    script_paths: [Option<String>; 4],
+    params: [Option<CannonParams>; 4],
+    eliminations: Vec<Elimination>,
+    #[serde(skip)]
+    journal: Option<Journal>,
+}
+
+impl Simulation {
+    pub fn new(seed: u64) -> Simulation {
+        Simulation::with_arena(seed, Arena::default())
+    }
+
+    /// Starts a match on a field set up differently from the default, see `Arena`.
+    pub fn with_arena(seed: u64, arena: Arena) -> Simulation {
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        Simulation {
+            grid: Grid::new(arena.neutral_band),
+            cannons: [
+                Cannon::new(1, colors::PLAYER1_CANNON, true, true, &arena, &mut rng),
+                Cannon::new(2, colors::PLAYER2_CANNON, false, true, &arena, &mut rng),
+                Cannon::new(3, colors::PLAYER3_CANNON, true, false, &arena, &mut rng),
+                Cannon::new(4, colors::PLAYER4_CANNON, false, false, &arena, &mut rng),
+            ],
+            bullets: Vec::new(),
+            plinkos: Simulation::new_plinkos(&arena),
+            field_rect: layout::field_rect(),
+            arena,
+            grid_image: None,
+            seed,
+            rng,
+            tick: 0,
+            aim_names: Default::default(),
+            fire_control_names: Default::default(),
+            bot_commands: Default::default(),
+            script_paths: Default::default(),
+            params: Default::default(),
+            eliminations: Vec::new(),
+            journal: None,
+        }
+    }
+
+    fn new_plinkos(arena: &Arena) -> [Plinko; 4] {
+        let colors = [
+            colors::PLAYER1_CANNON,
+            colors::PLAYER2_CANNON,
+            colors::PLAYER3_CANNON,
+            colors::PLAYER4_CANNON,
+        ];
+        [1, 2, 3, 4].map(|id| {
+            // players 2 and 4 are on the right panel
+            let is_mirrored = arena.plinko.mirror_right && id % 2 == 0;
+            Plinko::new(
+                id,
+                colors[(id - 1) as usize],
+                layout::plinko_position(id),
+                &arena.plinko,
+                is_mirrored,
+            )
+        })
+    }
+
+    /// Starts a new match from `seed`, keeping how each player is controlled.
+    pub fn restart(&mut self, seed: u64) {
+        let mut sim = Simulation::with_arena(seed, self.arena);
+        if let Some(path) = &self.grid_image {
+            // read again, so the match starts with the latest version of the image
+            if let Err(message) = sim.set_grid_image(path) {
+                eprintln!("{}", message);
+            }
+        }
+        for (i, cannon) in self.cannons.iter().enumerate() {
+            let id = (i + 1) as i8;
+            if !self.aim_names[i].is_empty() {
+                sim.set_aim(id, &self.aim_names[i])
+                    .expect("Aiming strategy was valid before");
+            }
+            if !self.fire_control_names[i].is_empty() {
+                sim.set_fire_control(id, &self.fire_control_names[i])
+                    .expect("Fire control was valid before");
+            }
+            if let Some(command) = &self.bot_commands[i] {
+                // a new process, so the bot starts the match afresh too
+                if let Err(message) = sim.set_bot(id, command) {
+                    eprintln!("{}", message);
+                }
+            }
+            if let Some(path) = &self.script_paths[i] {
+                // read again, so the match starts with the latest version of the file
+                if let Err(message) = sim.set_script(id, path) {
+                    eprintln!("{}", message);
+                }
+            }
+            if let Some(params) = self.params[i] {
+                sim.set_params(id, params);
+            }
+            if cannon.manual.is_some() {
+                sim.set_human(id);
+            }
+        }
+        *self = sim;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Number of updates since the match started.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn field_rect(&self) -> [f64; 4] {
+        self.field_rect
+    }
+
+    pub fn alive_count(&self) -> usize {
+        self.cannons.iter().filter(|c| c.is_alive).count()
+    }
+
+    /// Every cannon destroyed so far this match, in order.
+    pub fn eliminations(&self) -> &[Elimination] {
+        &self.eliminations
+    }
+
+    /// Selects the aiming strategy of a player by name, see `aim::STRATEGY_NAMES`.
+    pub fn set_aim(&mut self, cannon_id: i8, name: &str) -> Result<(), String> {
+        // each player gets its own stream of randomness, independent of the order of setup
+        let seed = self.seed.wrapping_add(cannon_id as u64);
+        let strategy = aim::strategy_by_name(name, seed).ok_or_else(|| {
+            format!(
+                "Unknown aiming strategy '{}', expected one of {}",
+                name,
+                aim::STRATEGY_NAMES.join(", ")
+            )
+        })?;
+        self.cannons[(cannon_id - 1) as usize].set_aim(strategy);
+        self.aim_names[(cannon_id - 1) as usize] = name.to_string();
+        Ok(())
+    }
+
+    /// Selects the fire control policy of a player by name, see `fire::POLICY_NAMES`.
+    pub fn set_fire_control(&mut self, cannon_id: i8, name: &str) -> Result<(), String> {
+        let policy = fire::policy_by_name(name).ok_or_else(|| {
+            format!(
+                "Unknown fire control '{}', expected one of {}",
+                name,
+                fire::POLICY_NAMES.join(", ")
+            )
+        })?;
+        self.cannons[(cannon_id - 1) as usize].set_fire_control(policy);
+        self.fire_control_names[(cannon_id - 1) as usize] = name.to_string();
+        Ok(())
+    }
+
+    /// Hands a cannon over to an external program, see `bot::spawn`.
+    pub fn set_bot(&mut self, cannon_id: i8, command: &str) -> Result<(), String> {
+        let (aim, fire_control) = bot::spawn(command)?;
+        let cannon = &mut self.cannons[(cannon_id - 1) as usize];
+        cannon.set_aim(aim);
+        cannon.set_fire_control(fire_control);
+        self.bot_commands[(cannon_id - 1) as usize] = Some(command.to_string());
+        Ok(())
+    }
+
+    /// Lets a Rhai script aim and fire a cannon, see `script::load`.
+    pub fn set_script(&mut self, cannon_id: i8, path: &str) -> Result<(), String> {
+        let (aim, fire_control) = script::load(path)?;
+        let cannon = &mut self.cannons[(cannon_id - 1) as usize];
+        cannon.set_aim(aim);
+        cannon.set_fire_control(fire_control);
+        self.script_paths[(cannon_id - 1) as usize] = Some(path.to_string());
+        Ok(())
+    }
+
+    /// Starts the match on a grid painted in an image instead of the four quarters, see
+    /// `Grid::load_image`.
+    pub fn set_grid_image(&mut self, path: &str) -> Result<(), String> {
+        self.grid = Grid::load_image(path)?;
+        self.grid_image = Some(path.to_string());
+        Ok(())
+    }
+
+    /// Plays a cannon with tuned numbers, see `Cannon::set_params`.
+    pub fn set_params(&mut self, cannon_id: i8, params: CannonParams) {
+        self.cannons[(cannon_id - 1) as usize].set_params(params);
+        self.params[(cannon_id - 1) as usize] = Some(params);
+    }
+
+    /// Hands a cannon over to a person, see `App::handle_seat_key`.
+    pub fn set_human(&mut self, cannon_id: i8) {
+        self.cannons[(cannon_id - 1) as usize].manual = Some(ManualControl::default());
+    }
+
+    pub fn aim_names(&self) -> &[String; 4] {
+        &self.aim_names
+    }
+
+    pub fn fire_control_names(&self) -> &[String; 4] {
+        &self.fire_control_names
+    }
+
+    /// Applies the input of a seat for the next tick, where `None` leaves the cannon to its
+    /// aiming strategy and fire control.
+    pub fn apply_input(&mut self, cannon_id: i8, input: Option<&SeatInput>) {
+        let i = (cannon_id - 1) as usize;
+        match input {
+            Some(input) if self.cannons[i].is_alive => {
+                self.cannons[i].manual = Some(input.manual);
+                if let Some(x) = input.drop_x {
+                    self.choose_drop(cannon_id, x);
+                }
+            }
+            _ => self.cannons[i].manual = None,
+        }
+    }
+
+    /// Hash of everything that decides how the match continues, so that two copies of the
+    /// simulation can cheaply check that they still agree.
+    pub fn checksum(&self) -> u64 {
+        // FNV-1a
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut add = |value: u64| {
+            for byte in value.to_le_bytes() {
+                hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        add(self.tick);
+        for cell in self.grid.cells.iter() {
+            add(*cell as u64);
+        }
+        for cannon in &self.cannons {
+            add(cannon.view().angle_deg.to_bits());
+            add(cannon.loaded_shots() as u64);
+            add(cannon.is_alive as u64);
+        }
+        for bullet in &self.bullets {
+            add(bullet.position[0].to_bits());
+            add(bullet.position[1].to_bits());
+        }
+        for plinko in &self.plinkos {
+            add(plinko.shot_count() as u64);
+            for puck in plinko.pucks() {
+                add(puck.position()[0].to_bits());
+                add(puck.position()[1].to_bits());
+            }
+        }
+        hash
+    }
+
+    /// One line per player describing how its fire control converted shots into cells.
+    pub fn fire_report(&self) -> Vec<String> {
+        self.cannons
+            .iter()
+            .map(|c| {
+                format!(
+                    "player {} fire {:<9} shots {:>5} cells {:>6} cells/shot {:.2}",
+                    c.id,
+                    c.fire_control_name(),
+                    c.stats.shots_fired,
+                    c.stats.cells_captured,
+                    c.stats.cells_per_shot()
+                )
+            })
+            .collect()
+    }
+
+    pub fn update(&mut self, delta_time: f64) {
+        self.advance(delta_time, None);
+    }
+
+    /// Plays a tick, with the cannons turning and firing as `recorded` says when given rather
+    /// than as their controls decide.
+    fn advance(&mut self, delta_time: f64, recorded: Option<&RecordedTick>) {
+        self.tick += 1;
+        let inputs = self
+            .journal
+            .as_mut()
+            .map(|journal| std::mem::take(&mut journal.inputs));
+
+        // move bullets
+        for bullet in &mut self.bullets {
+            bullet.step(self.field_rect);
+            if self.grid.check_collision(
+                bullet.position[0] - self.field_rect[0],
+                bullet.position[1] - self.field_rect[1],
+                bullet.cannon_id,
+            ) {
+                bullet.is_alive = false;
+                self.cannons[(bullet.cannon_id - 1) as usize]
+                    .stats
+                    .cells_captured += 1;
+            }
+            for cannon in &mut self.cannons {
+                let was_alive = cannon.is_alive;
+                cannon.check_collision(bullet);
+                if was_alive && !cannon.is_alive {
+                    self.eliminations.push(Elimination {
+                        victim: cannon.id,
+                        killer: bullet.cannon_id,
+                    });
+                }
+                if !cannon.is_alive {
+                    self.plinkos[(cannon.id - 1) as usize].is_alive = false;
+                }
+            }
+        }
+        self.bullets.retain(|b| b.is_alive);
+
+        // check plinkos for updates
+        let mut updates: Vec<PlinkoEvent> = Vec::new();
+        for plinko in &mut self.plinkos {
+            let func = |event: PlinkoEvent| {
+                updates.push(event);
+            };
+            plinko.update(delta_time, &mut self.rng, func);
+        }
+        for update in updates {
+            self.update_callback(update);
+        }
+
+        // let each cannon's fire control release the next bullet, then its strategy rotate it
+        let views = self
+            .cannons
+            .iter()
+            .map(Cannon::view)
+            .collect::<Vec<CannonView>>();
+        let arena = ArenaView {
+            grid: &self.grid,
+            cannons: &views,
+            bullets: &self.bullets,
+            field_rect: self.field_rect,
+        };
+        let mut fired = Vec::new();
+        for (i, cannon) in self.cannons.iter_mut().enumerate() {
+            let bullet = match recorded {
+                Some(tick) => cannon.shoot_as_recorded(tick.fired[i]),
+                None => cannon.shoot(&arena),
+            };
+            if let Some(b) = bullet {
+                fired.push(b);
+            }
+            match recorded {
+                Some(tick) => cannon.turn_to(tick.angles[i]),
+                None => cannon.turn(&arena),
+            }
+        }
+
+        if let (Some(journal), Some(inputs)) = (&mut self.journal, inputs) {
+            journal.ticks.push(RecordedTick {
+                inputs,
+                angles: self.cannons.each_ref().map(|c| c.view().angle_deg),
+                fired: [1, 2, 3, 4].map(|id| fired.iter().any(|b| b.cannon_id == id)),
+            });
+        }
+        self.bullets.extend(fired);
+    }
+
+    fn update_callback(&mut self, event: PlinkoEvent) {
+        // part of the tick itself, so it isn't noted as an input
+        self.cannons[(event.id - 1) as usize].load(event.num_shots);
+    }
+
+    pub fn load_cannon(&mut self, cannon_id: i8, num_shots: i32) {
+        self.apply(Input::Load {
+            id: cannon_id,
+            shots: num_shots,
+        });
+    }
+
+    /// Drops the next puck of a player's plinko board from `x`, see `Plinko::choose_drop`.
+    pub fn choose_drop(&mut self, cannon_id: i8, x: f64) {
+        self.apply(Input::Drop { id: cannon_id, x });
+    }
+
+    /// Turns the falling pucks of a player's plinko board, see `Plinko::nudge`.
+    pub fn nudge_plinko(&mut self, cannon_id: i8, degrees: f64) {
+        self.apply(Input::Nudge {
+            id: cannon_id,
+            degrees,
+        });
+    }
+}
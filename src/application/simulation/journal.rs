@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+
+use super::Simulation;
+
+/// Something done to the match from outside between two ticks.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Input {
+    Load { id: i8, shots: i32 },
+    Drop { id: i8, x: f64 },
+    Nudge { id: i8, degrees: f64 },
+}
+
+/// One tick as it was played: what was done to the match just before it, then where each
+/// cannon turned to and whether it fired.
+///
+/// That is everything a match takes from its players, so a snapshot and the ticks after it
+/// play out again without any of the strategies, bots or people that made the decisions.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecordedTick {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<Input>,
+    pub angles: [f64; 4],
+    pub fired: [bool; 4],
+}
+
+/// Ticks played since the journal was last taken, and inputs waiting for the next tick.
+#[derive(Default)]
+pub(super) struct Journal {
+    pub(super) inputs: Vec<Input>,
+    pub(super) ticks: Vec<RecordedTick>,
+}
+
+impl Simulation {
+    /// Starts noting every tick from now on, to be collected with `take_journal`.
+    ///
+    /// A match started afresh by `restart` or loaded from a snapshot doesn't keep a journal
+    /// until asked again.
+    pub fn start_journal(&mut self) {
+        self.journal = Some(Journal::default());
+    }
+
+    pub fn is_journaling(&self) -> bool {
+        self.journal.is_some()
+    }
+
+    /// The ticks played since the last call, oldest first.
+    pub fn take_journal(&mut self) -> Vec<RecordedTick> {
+        self.journal
+            .as_mut()
+            .map_or_else(Vec::new, |journal| std::mem::take(&mut journal.ticks))
+    }
+
+    /// Plays a tick the way it was recorded, see `RecordedTick`.
+    pub fn replay(&mut self, delta_time: f64, tick: &RecordedTick) {
+        for input in &tick.inputs {
+            self.apply(*input);
+        }
+        self.advance(delta_time, Some(tick));
+    }
+
+    /// Changes the match from outside, noting the change when keeping a journal.
+    pub(super) fn apply(&mut self, input: Input) {
+        if let Some(journal) = &mut self.journal {
+            journal.inputs.push(input);
+        }
+        match input {
+            Input::Load { id, shots } => self.cannons[(id - 1) as usize].load(shots),
+            Input::Drop { id, x } => self.plinkos[(id - 1) as usize].choose_drop(x),
+            Input::Nudge { id, degrees } => self.plinkos[(id - 1) as usize].nudge(degrees),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{Elimination, Simulation};
+
+/// A saved match, with what the aiming strategy and fire control of each cannon remember.
+#[derive(Serialize)]
+struct SavedSnapshot<'a> {
+    sim: &'a Simulation,
+    controls: [[Value; 2]; 4],
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    sim: Simulation,
+    controls: [[Value; 2]; 4],
+}
+
+impl Simulation {
+    /// Everything about the match as JSON, so that `from_snapshot` can carry on from exactly
+    /// this tick.
+    pub fn snapshot(&self) -> String {
+        let snapshot = SavedSnapshot {
+            sim: self,
+            controls: self.cannons.each_ref().map(|c| c.controls_state()),
+        };
+        serde_json::to_string(&snapshot).expect("The match is plain data")
+    }
+
+    /// Carries on a match saved by `snapshot`.
+    ///
+    /// Bots are started again and scripts read again from their files, so they only keep
+    /// what they last decided, and a bot that remembers more may play on differently.
+    pub fn from_snapshot(text: &str) -> Result<Simulation, String> {
+        let Snapshot { mut sim, controls } =
+            serde_json::from_str(text).map_err(|e| format!("Invalid snapshot: {}", e))?;
+        sim.check_ids()?;
+        for (i, state) in controls.iter().enumerate() {
+            let id = (i + 1) as i8;
+            if !sim.aim_names[i].is_empty() {
+                sim.set_aim(id, &sim.aim_names[i].clone())?;
+            }
+            if !sim.fire_control_names[i].is_empty() {
+                sim.set_fire_control(id, &sim.fire_control_names[i].clone())?;
+            }
+            if let Some(command) = sim.bot_commands[i].clone() {
+                sim.set_bot(id, &command)?;
+            }
+            if let Some(path) = sim.script_paths[i].clone() {
+                sim.set_script(id, &path)?;
+            }
+            if let Some(params) = sim.params[i] {
+                sim.set_params(id, params);
+            }
+            sim.cannons[i].restore_controls(state);
+        }
+        Ok(sim)
+    }
+
+    /// The match saved by `snapshot` as it stood, to be shown rather than played on: the
+    /// cannons keep the default strategies, and no bot or script is started.
+    pub fn view_snapshot(text: &str) -> Result<Simulation, String> {
+        let Snapshot { sim, .. } =
+            serde_json::from_str(text).map_err(|e| format!("Invalid snapshot: {}", e))?;
+        sim.check_ids()?;
+        Ok(sim)
+    }
+
+    /// The ids in a snapshot index the cannons and plinkos, so one that doesn't match its seat
+    /// would panic, or quietly hand shots to the wrong player, later in the match.
+    fn check_ids(&self) -> Result<(), String> {
+        let seats = 1..=self.cannons.len() as i8;
+        for (i, (cannon, plinko)) in self.cannons.iter().zip(&self.plinkos).enumerate() {
+            let id = (i + 1) as i8;
+            if cannon.id != id {
+                return Err(format!(
+                    "Invalid snapshot: cannon {} has id {}",
+                    id, cannon.id
+                ));
+            }
+            if plinko.id() != id {
+                return Err(format!(
+                    "Invalid snapshot: plinko {} has id {}",
+                    id,
+                    plinko.id()
+                ));
+            }
+        }
+        if let Some(bullet) = self.bullets.iter().find(|b| !seats.contains(&b.cannon_id)) {
+            return Err(format!(
+                "Invalid snapshot: a bullet was fired by cannon {}",
+                bullet.cannon_id
+            ));
+        }
+        let is_valid = |e: &Elimination| seats.contains(&e.victim) && seats.contains(&e.killer);
+        if let Some(e) = self.eliminations.iter().find(|e| !is_valid(e)) {
+            return Err(format!(
+                "Invalid snapshot: cannon {} was eliminated by cannon {}",
+                e.victim, e.killer
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn save_snapshot(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.snapshot() + "\n")
+            .map_err(|e| format!("Unable to write snapshot '{}': {}", path, e))
+    }
+
+    pub fn load_snapshot(path: &str) -> Result<Simulation, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read snapshot '{}': {}", path, e))?;
+        Simulation::from_snapshot(&text).map_err(|e| format!("{} in '{}'", e, path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELTA_TIME: f64 = 1.0 / 120.0;
+
+    /// A match some way in, with bullets in flight and a cannon destroyed.
+    fn played() -> Simulation {
+        let mut sim = Simulation::new(7);
+        for id in 1..=4 {
+            sim.set_aim(id, "nearest").unwrap();
+        }
+        while sim.eliminations().is_empty() || sim.bullets.is_empty() {
+            sim.update(DELTA_TIME);
+        }
+        sim
+    }
+
+    fn with(text: &str, change: impl FnOnce(&mut Value)) -> String {
+        let mut snapshot = serde_json::from_str::<Value>(text).unwrap();
+        change(&mut snapshot["sim"]);
+        snapshot.to_string()
+    }
+
+    #[test]
+    fn snapshot_plays_on_the_same() {
+        let mut sim = played();
+        let mut loaded = Simulation::from_snapshot(&sim.snapshot()).unwrap();
+        assert_eq!(loaded.tick(), sim.tick());
+        assert_eq!(loaded.checksum(), sim.checksum());
+        for _ in 0..2000 {
+            sim.update(DELTA_TIME);
+            loaded.update(DELTA_TIME);
+            assert_eq!(loaded.checksum(), sim.checksum());
+        }
+    }
+
+    #[test]
+    fn snapshot_with_bad_ids_is_rejected() {
+        let text = played().snapshot();
+        let bad = [
+            with(&text, |sim| sim["cannons"][2]["id"] = 1.into()),
+            with(&text, |sim| sim["plinkos"][0]["id"] = 4.into()),
+            with(&text, |sim| sim["bullets"][0]["cannon_id"] = 9.into()),
+            with(&text, |sim| sim["bullets"][0]["cannon_id"] = 0.into()),
+            with(&text, |sim| sim["eliminations"][0]["killer"] = (-1).into()),
+            with(&text, |sim| sim["eliminations"][0]["victim"] = 5.into()),
+        ];
+        for text in &bad {
+            assert!(Simulation::from_snapshot(text).is_err());
+            assert!(Simulation::view_snapshot(text).is_err());
+        }
+        assert!(Simulation::from_snapshot(&text).is_ok());
+    }
+}
@@ -0,0 +1,261 @@
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+use tungstenite::Message;
+
+use super::Simulation;
+
+#[derive(Serialize, Deserialize)]
+struct CannonState {
+    id: i8,
+    angle_deg: f64,
+    loaded_shots: i32,
+    is_alive: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BulletState {
+    cannon_id: i8,
+    position: [f64; 2],
+}
+
+#[derive(Serialize, Deserialize)]
+struct PlinkoState {
+    id: i8,
+    shot_count: i32,
+    pucks: Vec<[f64; 2]>,
+}
+
+/// Everything besides the grid, which is small enough to send whole every time.
+#[derive(Serialize, Deserialize)]
+struct Objects {
+    cannons: Vec<CannonState>,
+    bullets: Vec<BulletState>,
+    plinkos: Vec<PlinkoState>,
+}
+
+impl Objects {
+    fn new(sim: &Simulation) -> Objects {
+        Objects {
+            cannons: sim
+                .cannons
+                .iter()
+                .map(|c| CannonState {
+                    id: c.id,
+                    angle_deg: c.view().angle_deg,
+                    loaded_shots: c.loaded_shots(),
+                    is_alive: c.is_alive,
+                })
+                .collect(),
+            bullets: sim
+                .bullets
+                .iter()
+                .map(|b| BulletState {
+                    cannon_id: b.cannon_id,
+                    position: b.position,
+                })
+                .collect(),
+            plinkos: sim
+                .plinkos
+                .iter()
+                .enumerate()
+                .map(|(i, p)| PlinkoState {
+                    id: (i + 1) as i8,
+                    shot_count: p.shot_count(),
+                    pucks: p.pucks().iter().map(|puck| puck.position()).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// What viewers receive, one JSON text message each. Cells are indexed like `Grid::cells`,
+/// column by column, and a delta only lists the cells that changed owner.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Update {
+    Snapshot {
+        tick: u64,
+        cells: Vec<i8>,
+        #[serde(flatten)]
+        objects: Objects,
+    },
+    Delta {
+        tick: u64,
+        cells: Vec<(usize, i8)>,
+        #[serde(flatten)]
+        objects: Objects,
+    },
+}
+
+/// Streams the match to any number of WebSocket viewers, so it can be shown on other screens.
+///
+/// Each viewer starts with a snapshot of the whole state and then receives deltas. A viewer
+/// that can't keep up is disconnected, and gets a fresh snapshot when it connects again.
+pub struct SpectatorServer {
+    // connections that have yet to finish the WebSocket handshake
+    joined: Receiver<TcpStream>,
+    viewers: Vec<SyncSender<Arc<String>>>,
+    cells: Vec<i8>,
+    last_tick: Option<u64>,
+}
+
+impl SpectatorServer {
+    // 30 updates per second at normal speed
+    const PUBLISH_TICKS: u64 = 4;
+    // updates waiting for a slow viewer before it is dropped
+    const MAX_QUEUED: usize = 64;
+
+    pub fn start(port: u16) -> Result<SpectatorServer, String> {
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .map_err(|e| format!("Unable to listen for spectators on port {}: {}", port, e))?;
+        let (sender, joined) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = stream.set_nodelay(true);
+                if sender.send(stream).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(SpectatorServer {
+            joined,
+            viewers: Vec::new(),
+            cells: Vec::new(),
+            last_tick: None,
+        })
+    }
+
+    /// Sends what changed since the last call to every viewer, at most every few ticks, and
+    /// lets in viewers that connected since.
+    pub fn publish(&mut self, sim: &Simulation) {
+        let tick = sim.tick();
+        // a restart starts counting ticks again
+        let is_due = self.last_tick.is_none_or(|last_tick| {
+            tick < last_tick || tick >= last_tick + SpectatorServer::PUBLISH_TICKS
+        });
+        if is_due {
+            self.send_delta(sim);
+        }
+        // even while the match is paused, from the cells the next delta starts from
+        while let Ok(stream) = self.joined.try_recv() {
+            self.add_viewer(stream, sim);
+        }
+    }
+
+    fn send_delta(&mut self, sim: &Simulation) {
+        let tick = sim.tick();
+        self.last_tick = Some(tick);
+        if !self.viewers.is_empty() {
+            let cells = sim
+                .grid
+                .cells
+                .iter()
+                .zip(&self.cells)
+                .enumerate()
+                .filter(|(_, (new, old))| new != old)
+                .map(|(i, (new, _))| (i, *new))
+                .collect();
+            let delta = Update::Delta {
+                tick,
+                cells,
+                objects: Objects::new(sim),
+            };
+            let text = Arc::new(serde_json::to_string(&delta).expect("Unable to encode delta"));
+            self.viewers
+                .retain(|viewer| viewer.try_send(text.clone()).is_ok());
+        }
+        self.cells = sim.grid.cells.to_vec();
+    }
+
+    fn add_viewer(&mut self, stream: TcpStream, sim: &Simulation) {
+        let snapshot = Update::Snapshot {
+            tick: sim.tick(),
+            cells: self.cells.clone(),
+            objects: Objects::new(sim),
+        };
+        let text = serde_json::to_string(&snapshot).expect("Unable to encode snapshot");
+        let (sender, updates) = mpsc::sync_channel::<Arc<String>>(SpectatorServer::MAX_QUEUED);
+        // a viewer's own thread does the handshake and the writing, so a slow network never
+        // holds up the game or the other viewers
+        thread::spawn(move || {
+            let Ok(mut socket) = tungstenite::accept(stream) else {
+                return;
+            };
+            if socket.send(Message::text(text)).is_err() {
+                return;
+            }
+            for update in updates {
+                if socket.send(Message::text(update.as_str())).is_err() {
+                    return;
+                }
+            }
+            let _ = socket.close(None);
+        });
+        self.viewers.push(sender);
+    }
+}
+
+/// Headless viewer that follows a spectator stream and prints a line per update with the
+/// territory, cannons and plinkos of each player.
+pub fn watch(url: &str) -> Result<(), String> {
+    let (mut socket, _) =
+        tungstenite::connect(url).map_err(|e| format!("Unable to connect to {}: {}", url, e))?;
+    let mut cells: Vec<i8> = Vec::new();
+    loop {
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => continue,
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(e) => return Err(format!("Lost the stream: {}", e)),
+        };
+        let (tick, changed, objects) = match serde_json::from_str(text.as_str()) {
+            Ok(Update::Snapshot {
+                tick,
+                cells: all,
+                objects,
+            }) => {
+                cells = all;
+                (tick, cells.len(), objects)
+            }
+            Ok(Update::Delta {
+                tick,
+                cells: changes,
+                objects,
+            }) => {
+                for (i, owner) in &changes {
+                    if let Some(cell) = cells.get_mut(*i) {
+                        *cell = *owner;
+                    }
+                }
+                (tick, changes.len(), objects)
+            }
+            Err(e) => return Err(format!("Unexpected update: {}", e)),
+        };
+
+        let mut line = format!(
+            "tick {:>6} cells {:>4} bullets {:>3}",
+            tick,
+            changed,
+            objects.bullets.len()
+        );
+        for (cannon, plinko) in objects.cannons.iter().zip(&objects.plinkos) {
+            let territory = cells.iter().filter(|c| **c == cannon.id).count();
+            line += &format!(
+                " | {} {} {:>4} {:>6.1}° loaded {:>3} x{} pucks {}",
+                cannon.id,
+                if cannon.is_alive { "alive" } else { "dead " },
+                territory,
+                cannon.angle_deg,
+                cannon.loaded_shots,
+                plinko.shot_count,
+                plinko.pucks.len()
+            );
+        }
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,81 @@
+use opengl_graphics::GlGraphics;
+
+use super::colors;
+use super::common;
+use super::layout;
+
+// the rate at which the window updates the match
+pub const TICKS_PER_SECOND: u64 = 120;
+const BAR_HEIGHT: f64 = 8.0;
+const MARK_WIDTH: f64 = 2.0;
+
+/// The timeline bar in the frame below the field, as [x, y, width, height].
+fn bar_rect() -> [f64; 4] {
+    let [left, top, width, height] = layout::field_rect();
+    let border = common::BORDER_SIZE as f64;
+    [
+        left,
+        top + height + (border - BAR_HEIGHT) / 2.0,
+        width,
+        BAR_HEIGHT,
+    ]
+}
+
+/// Whether a point in logical units is on the timeline bar, or near enough to grab it.
+pub fn is_on_bar(position: [f64; 2]) -> bool {
+    let [left, top, width, height] = bar_rect();
+    let margin = common::BORDER_SIZE as f64 / 2.0;
+    position[0] >= left
+        && position[0] <= left + width
+        && (position[1] - (top + height / 2.0)).abs() <= margin
+}
+
+/// How far along the bar a point in logical units is, from 0 at the left end to 1 at the right.
+pub fn share_at(x: f64) -> f64 {
+    let [left, _, width, _] = bar_rect();
+    ((x - left) / width).clamp(0.0, 1.0)
+}
+
+/// Draws the bar filled up to `share` of its length, with a handle there.
+pub fn draw(share: f64, c: &graphics::Context, gl: &mut GlGraphics) {
+    let [left, top, width, height] = bar_rect();
+    graphics::rectangle(
+        graphics::color::hex(colors::GRID),
+        [left, top, width, height],
+        c.transform,
+        gl,
+    );
+    graphics::rectangle(
+        graphics::color::hex(colors::HUD),
+        [left, top, width * share, height],
+        c.transform,
+        gl,
+    );
+    graphics::rectangle(
+        graphics::color::hex(colors::DEBUG),
+        [
+            left + width * share - height / 2.0,
+            top - height / 2.0,
+            height,
+            height * 2.0,
+        ],
+        c.transform,
+        gl,
+    );
+}
+
+/// Draws a thin mark across the bar at `share` of its length, e.g. for a bookmark.
+pub fn draw_mark(share: f64, color: &str, c: &graphics::Context, gl: &mut GlGraphics) {
+    let [left, top, width, height] = bar_rect();
+    graphics::rectangle(
+        graphics::color::hex(color),
+        [
+            left + width * share - MARK_WIDTH / 2.0,
+            top - height / 2.0,
+            MARK_WIDTH,
+            height * 2.0,
+        ],
+        c.transform,
+        gl,
+    );
+}
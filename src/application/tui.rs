@@ -0,0 +1,122 @@
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+
+use super::colors;
+use super::common;
+use super::Simulation;
+
+/// Draws a `Simulation` to a terminal with 24-bit ANSI colors, two grid rows per text row.
+pub struct TuiRenderer {
+    out: io::Stdout,
+}
+
+impl TuiRenderer {
+    const NAMES: [&'static str; 4] = ["Red", "Green", "Blue", "Yellow"];
+    const HALF_BLOCK: char = '▀';
+    const CANNON_MARK: char = '◉';
+    const BULLET_MARK: char = '•';
+
+    pub fn new() -> TuiRenderer {
+        TuiRenderer { out: io::stdout() }
+    }
+
+    pub fn start(&mut self) -> io::Result<()> {
+        // clear the screen and hide the cursor
+        write!(self.out, "\x1b[2J\x1b[?25l")?;
+        self.out.flush()
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        // reset colors and show the cursor again
+        writeln!(self.out, "\x1b[0m\x1b[?25h")?;
+        self.out.flush()
+    }
+
+    /// Draws the match with a line for each of `notes` below it, such as connection status.
+    pub fn render(&mut self, sim: &Simulation, notes: &[String]) -> io::Result<()> {
+        let frame = TuiRenderer::draw(sim, notes);
+        write!(self.out, "\x1b[H{}", frame)?;
+        self.out.flush()
+    }
+
+    fn draw(sim: &Simulation, notes: &[String]) -> String {
+        let edge = (common::CELL_EDGES * 2) as usize;
+        let rows = edge / 2;
+        let field = sim.field_rect();
+
+        // cannons and bullets are drawn on top of the territory
+        let mut marks: Vec<Option<(char, [u8; 3])>> = vec![None; edge * rows];
+        let mut mark = |position: [f64; 2], symbol: char, hex: &str| {
+            let x = ((position[0] - field[0]) / common::CELL_WIDTH as f64) as i32;
+            let y = ((position[1] - field[1]) / common::CELL_WIDTH as f64) as i32;
+            if x >= 0 && y >= 0 && (x as usize) < edge && (y as usize) < edge {
+                marks[(y as usize / 2) * edge + x as usize] = Some((symbol, colors::rgb(hex)));
+            }
+        };
+        for bullet in &sim.bullets {
+            mark(
+                bullet.position,
+                TuiRenderer::BULLET_MARK,
+                colors::cannon(bullet.cannon_id),
+            );
+        }
+        for cannon in sim.cannons.iter().filter(|c| c.is_alive) {
+            mark(
+                cannon.position(),
+                TuiRenderer::CANNON_MARK,
+                colors::cannon(cannon.id),
+            );
+        }
+
+        let mut frame = String::new();
+        for row in 0..rows {
+            for x in 0..edge {
+                let top = colors::rgb(colors::field(
+                    sim.grid.cells[common::calc_logical_index(x as i32, (row * 2) as i32)],
+                ));
+                let bottom = colors::rgb(colors::field(
+                    sim.grid.cells[common::calc_logical_index(x as i32, (row * 2 + 1) as i32)],
+                ));
+                match marks[row * edge + x] {
+                    Some((symbol, color)) => {
+                        push_colored(&mut frame, symbol, color, top);
+                    }
+                    None => {
+                        push_colored(&mut frame, TuiRenderer::HALF_BLOCK, top, bottom);
+                    }
+                }
+            }
+            frame.push_str("\x1b[0m\x1b[K\n");
+        }
+
+        for (i, cannon) in sim.cannons.iter().enumerate() {
+            let plinko = &sim.plinkos[i];
+            let [r, g, b] = colors::rgb(colors::cannon(cannon.id));
+            let _ = writeln!(
+                frame,
+                "\x1b[38;2;{};{};{}m{:<7}\x1b[0m {:<5} territory {:>5}  plinko x{:<6} loaded {:<6} aim {}\x1b[K",
+                r,
+                g,
+                b,
+                TuiRenderer::NAMES[i],
+                if cannon.is_alive { "alive" } else { "dead" },
+                sim.grid.territory(cannon.id),
+                plinko.shot_count(),
+                cannon.loaded_shots(),
+                cannon.aim_name(),
+            );
+        }
+        for note in notes {
+            let _ = writeln!(frame, "{}\x1b[K", note);
+        }
+        frame
+    }
+}
+
+fn push_colored(frame: &mut String, symbol: char, fg: [u8; 3], bg: [u8; 3]) {
+    let _ = write!(
+        frame,
+        "\x1b[38;2;{};{};{};48;2;{};{};{}m{}",
+        fg[0], fg[1], fg[2], bg[0], bg[1], bg[2], symbol
+    );
+}
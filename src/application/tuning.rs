@@ -0,0 +1,237 @@
+use std::thread;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::cannon::{AimWeights, CannonParams};
+use super::Simulation;
+
+/// How a search for strong parameters for one seat is run.
+pub struct Search {
+    pub seat: i8,
+    // how the seat plays untuned, the first candidate
+    pub defaults: CannonParams,
+    pub generations: usize,
+    pub population: usize,
+    // matches each candidate plays per generation, all candidates on the same seeds
+    pub matches: usize,
+    pub seed: u64,
+    pub delta_time: f64,
+    // matches that last longer are scored as they stand
+    pub max_ticks: u64,
+}
+
+/// How a generation of candidates did.
+pub struct Generation {
+    pub number: usize,
+    pub best: f64,
+    pub mean: f64,
+    pub worst: f64,
+    pub params: CannonParams,
+}
+
+impl Generation {
+    /// One line of the fitness report.
+    pub fn report(&self) -> String {
+        let p = &self.params;
+        format!(
+            "generation {:>3}  best {:>7.1}  mean {:>7.1}  worst {:>7.1}  sweep {:>4.1} turn {:.2} delay {:>2} weights {:.2}/{:.2}/{:.2}",
+            self.number,
+            self.best,
+            self.mean,
+            self.worst,
+            p.sweep,
+            p.turn_speed,
+            p.fire_delay,
+            p.aim_weights.sweep,
+            p.aim_weights.nearest,
+            p.aim_weights.territory
+        )
+    }
+}
+
+const SWEEP: (f64, f64) = (10.0, 90.0);
+const TURN_SPEED: (f64, f64) = (0.05, 1.5);
+const FIRE_DELAY: (i32, i32) = (0, 20);
+const WEIGHT: (f64, f64) = (0.0, 1.0);
+
+// the best candidates carried over unchanged to the next generation
+const ELITE: usize = 2;
+const TOURNAMENT: usize = 3;
+const MUTATION_RATE: f64 = 0.25;
+// the largest mutation, as a share of the range of a number
+const MUTATION_SIZE: f64 = 0.2;
+// on top of the cells held at the end, for still standing
+const SURVIVAL_BONUS: f64 = 1000.0;
+
+/// Evolves parameters for `search.seat` against the opponents that `new_match` sets up,
+/// calling `report` after every generation and returning the best candidate seen.
+///
+/// A candidate's fitness is the mean over its matches of the cells its seat holds at the end,
+/// plus `SURVIVAL_BONUS` when it is still standing. The same `search.seed` always gives the
+/// same result, as long as `new_match` plays out the same from the same seed, which matches
+/// with bots don't.
+pub fn evolve<F>(
+    search: &Search,
+    new_match: F,
+    mut report: impl FnMut(&Generation),
+) -> Result<CannonParams, String>
+where
+    F: Fn(u64) -> Result<Simulation, String> + Sync,
+{
+    let mut rng = StdRng::seed_from_u64(search.seed);
+    // the defaults compete from the start, so the search can only do better than them
+    let mut population = vec![search.defaults];
+    while population.len() < search.population {
+        population.push(random_params(&mut rng));
+    }
+
+    let mut best: Option<(f64, CannonParams)> = None;
+    for number in 1..=search.generations {
+        let seeds = (0..search.matches).map(|_| rng.gen()).collect::<Vec<u64>>();
+        let scores = evaluate(search, &new_match, &population, &seeds)?;
+        let mut ranked = population.iter().copied().zip(scores).collect::<Vec<_>>();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let generation = Generation {
+            number,
+            best: ranked[0].1,
+            mean: ranked.iter().map(|(_, score)| score).sum::<f64>() / ranked.len() as f64,
+            worst: ranked[ranked.len() - 1].1,
+            params: ranked[0].0,
+        };
+        report(&generation);
+        if best.is_none_or(|(score, _)| generation.best > score) {
+            best = Some((generation.best, generation.params));
+        }
+
+        population = ranked
+            .iter()
+            .take(ELITE)
+            .map(|(params, _)| *params)
+            .collect();
+        while population.len() < search.population {
+            let a = select(&ranked, &mut rng);
+            let b = select(&ranked, &mut rng);
+            population.push(mutate(crossover(a, b, &mut rng), &mut rng));
+        }
+    }
+    best.map(|(_, params)| params)
+        .ok_or_else(|| "There must be at least one generation".to_string())
+}
+
+/// Plays every candidate on every seed, each group of candidates on its own thread.
+fn evaluate<F>(
+    search: &Search,
+    new_match: &F,
+    population: &[CannonParams],
+    seeds: &[u64],
+) -> Result<Vec<f64>, String>
+where
+    F: Fn(u64) -> Result<Simulation, String> + Sync,
+{
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let group = population.len().div_ceil(threads).max(1);
+    thread::scope(|scope| {
+        let handles = population
+            .chunks(group)
+            .map(|candidates| {
+                scope.spawn(move || {
+                    candidates
+                        .iter()
+                        .map(|params| fitness(search, new_match, *params, seeds))
+                        .collect::<Result<Vec<f64>, String>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut scores = Vec::with_capacity(population.len());
+        for handle in handles {
+            scores.extend(handle.join().expect("A match panicked")?);
+        }
+        Ok(scores)
+    })
+}
+
+fn fitness<F>(
+    search: &Search,
+    new_match: &F,
+    params: CannonParams,
+    seeds: &[u64],
+) -> Result<f64, String>
+where
+    F: Fn(u64) -> Result<Simulation, String>,
+{
+    let mut total = 0.0;
+    for seed in seeds {
+        let mut sim = new_match(*seed)?;
+        sim.set_params(search.seat, params);
+        while sim.alive_count() > 1 && sim.tick() < search.max_ticks {
+            sim.update(search.delta_time);
+        }
+        total += sim.grid.territory(search.seat) as f64;
+        if sim.cannons[(search.seat - 1) as usize].is_alive {
+            total += SURVIVAL_BONUS;
+        }
+    }
+    Ok(total / seeds.len().max(1) as f64)
+}
+
+fn random_params(rng: &mut StdRng) -> CannonParams {
+    CannonParams {
+        sweep: rng.gen_range(SWEEP.0..=SWEEP.1),
+        turn_speed: rng.gen_range(TURN_SPEED.0..=TURN_SPEED.1),
+        fire_delay: rng.gen_range(FIRE_DELAY.0..=FIRE_DELAY.1),
+        aim_weights: AimWeights {
+            sweep: rng.gen_range(WEIGHT.0..=WEIGHT.1),
+            nearest: rng.gen_range(WEIGHT.0..=WEIGHT.1),
+            territory: rng.gen_range(WEIGHT.0..=WEIGHT.1),
+        },
+    }
+}
+
+/// The best of a few candidates picked at random.
+fn select(ranked: &[(CannonParams, f64)], rng: &mut StdRng) -> CannonParams {
+    (0..TOURNAMENT)
+        .map(|_| ranked[rng.gen_range(0..ranked.len())])
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(params, _)| params)
+        .expect("Tournaments are never empty")
+}
+
+/// Each number from either parent.
+fn crossover(a: CannonParams, b: CannonParams, rng: &mut StdRng) -> CannonParams {
+    let mut pick = |x: f64, y: f64| if rng.gen() { x } else { y };
+    CannonParams {
+        sweep: pick(a.sweep, b.sweep),
+        turn_speed: pick(a.turn_speed, b.turn_speed),
+        fire_delay: pick(a.fire_delay as f64, b.fire_delay as f64) as i32,
+        aim_weights: AimWeights {
+            sweep: pick(a.aim_weights.sweep, b.aim_weights.sweep),
+            nearest: pick(a.aim_weights.nearest, b.aim_weights.nearest),
+            territory: pick(a.aim_weights.territory, b.aim_weights.territory),
+        },
+    }
+}
+
+/// Nudges some of the numbers, keeping each within its range.
+fn mutate(params: CannonParams, rng: &mut StdRng) -> CannonParams {
+    let mut nudge = |value: f64, (low, high): (f64, f64)| {
+        if rng.gen_bool(MUTATION_RATE) {
+            let change = rng.gen_range(-1.0..=1.0) * MUTATION_SIZE * (high - low);
+            (value + change).clamp(low, high)
+        } else {
+            value
+        }
+    };
+    let delay = (FIRE_DELAY.0 as f64, FIRE_DELAY.1 as f64);
+    CannonParams {
+        sweep: nudge(params.sweep, SWEEP),
+        turn_speed: nudge(params.turn_speed, TURN_SPEED),
+        fire_delay: nudge(params.fire_delay as f64, delay).round() as i32,
+        aim_weights: AimWeights {
+            sweep: nudge(params.aim_weights.sweep, WEIGHT),
+            nearest: nudge(params.aim_weights.nearest, WEIGHT),
+            territory: nudge(params.aim_weights.territory, WEIGHT),
+        },
+    }
+}
@@ -1,32 +1,194 @@
+extern crate glutin;
 extern crate glutin_window;
 extern crate opengl_graphics;
 extern crate piston;
 
+use glutin::window::Fullscreen;
 use glutin_window::GlutinWindow;
 use opengl_graphics::{GlGraphics, OpenGL};
 use piston::window::WindowSettings;
-use piston::{EventSettings, Events, PressEvent, RenderEvent, UpdateEvent};
+use piston::{
+    EventLoop, EventSettings, Events, MouseCursorEvent, PressEvent, ReleaseEvent, RenderEvent,
+    UpdateEvent,
+};
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 mod application;
+mod options;
 
-use application::App;
+use application::arena::Arena;
+use application::balance::{self, Study};
+use application::chat::ChatClient;
+use application::control::ControlServer;
+use application::environment::{self, Environment};
+use application::highlights::{self, Highlights};
+use application::input_map::{Action, InputMap};
+use application::ladder::{self, Ladder, Season};
+use application::lockstep::Lockstep;
+use application::replay::{Recorder, Replay};
+use application::spectator::{self, SpectatorServer};
+use application::tui::TuiRenderer;
+use application::tuning::{self, Search};
+use application::{App, CannonParams, Simulation};
+use options::Options;
+
+const UPDATES_PER_SECOND: u64 = 120;
+const TUI_FRAMES_PER_SECOND: u64 = 20;
+const DEFAULT_CONTROLS: &str = "controls.cfg";
 
 fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+    if let Some(url) = &options.watch {
+        if let Err(message) = spectator::watch(url) {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if options.balance {
+        if let Err(message) = check_balance(&options) {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(seat) = options.tune {
+        if let Err(message) = tune(&options, seat) {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if options.season {
+        if let Err(message) = play_season(&options) {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if options.leaderboard {
+        match Ladder::load(&options.ladder) {
+            Ok(ladder) => ladder
+                .leaderboard()
+                .iter()
+                .for_each(|line| println!("{}", line)),
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(count) = options.envs {
+        if let Err(message) = serve_environments(&options, count) {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let (Some(path), Some(dir)) = (&options.replay, &options.clips) {
+        // clips of a recording are cut without watching it
+        let exported =
+            open_replay(path).and_then(|(mut replay, _)| save_clips(&mut replay, dir, &options));
+        if let Err(message) = exported {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let started = match &options.replay {
+        Some(path) => {
+            open_replay(path).map(|(replay, sim)| (sim, Connections::default(), Some(replay)))
+        }
+        None => start_match(&options).map(|(sim, connections)| (sim, connections, None)),
+    };
+    let (sim, connections, replay) = match started {
+        Ok(started) => started,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+    let recorder = match options.record.as_deref().map(Recorder::create).transpose() {
+        Ok(recorder) => recorder,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+
+    let highlights = options.highlights.then(Highlights::new);
+
+    if options.tui {
+        run_tui(sim, connections, recorder, highlights);
+        clip_recording(&options);
+        return;
+    }
+
+    let input = match load_input_map(&options) {
+        Ok(input) => input,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+
     let opengl = OpenGL::V3_2;
 
     let mut window: GlutinWindow = WindowSettings::new(
         "Color Battle",
         [App::get_width() as f64, App::get_height() as f64],
     )
-    .resizable(false)
+    .resizable(true)
+    .fullscreen(options.fullscreen)
     .graphics_api(opengl)
     .exit_on_esc(true)
     .build()
     .unwrap();
 
-    let mut app = App::new(GlGraphics::new(opengl));
+    let mut app = App::new(GlGraphics::new(opengl), sim, input);
+    if let Some(path) = &options.snapshot {
+        app.set_snapshot_path(path.clone());
+    }
+    if let Some(path) = &options.grid_out {
+        app.set_grid_path(path.clone());
+    }
+    if let Some(seconds) = options.rewind {
+        app.set_rewind_seconds(seconds);
+    }
+    if let Some(id) = options.mouse {
+        app.set_mouse_seat(id);
+    }
+    if let Some(lockstep) = connections.lockstep {
+        app.set_lockstep(lockstep);
+    }
+    if let Some(spectators) = connections.spectators {
+        app.set_spectators(spectators);
+    }
+    if let Some(chat) = connections.chat {
+        app.set_chat(chat);
+    }
+    if let Some(control) = connections.control {
+        app.set_control(control);
+    }
+    if let Some(recorder) = recorder {
+        app.set_recorder(recorder);
+    }
+    if let Some(replay) = replay {
+        app.set_replay(replay);
+    }
+    if let Some(highlights) = highlights {
+        app.set_highlights(highlights);
+    }
 
-    let mut events = Events::new(EventSettings::new());
+    let mut events = Events::new(EventSettings::new().ups(UPDATES_PER_SECOND));
     while let Some(e) = events.next(&mut window) {
         if let Some(args) = e.render_args() {
             app.render(&args);
@@ -37,7 +199,380 @@ fn main() {
         }
 
         if let Some(button) = e.press_args() {
-            app.handle_button(&button);
+            if app.handle_button(&button) == Some(Action::ToggleFullscreen) {
+                toggle_fullscreen(&window);
+            }
+        }
+
+        if let Some(button) = e.release_args() {
+            app.handle_release(&button);
+        }
+
+        if let Some(position) = e.mouse_cursor_args() {
+            app.handle_cursor(position);
         }
     }
+    // the recording is only complete once the app lets go of it
+    drop(app);
+    clip_recording(&options);
+}
+
+/// Uses the controls file given on the command line, or `controls.cfg` when there is one.
+fn load_input_map(options: &Options) -> Result<InputMap, String> {
+    match &options.controls {
+        Some(path) => InputMap::load(path),
+        None if Path::new(DEFAULT_CONTROLS).exists() => InputMap::load(DEFAULT_CONTROLS),
+        None => Ok(InputMap::new()),
+    }
+}
+
+fn toggle_fullscreen(window: &GlutinWindow) {
+    let window = window.ctx.window();
+    if window.fullscreen().is_some() {
+        window.set_fullscreen(None);
+    } else {
+        let monitor = window.current_monitor();
+        window.set_fullscreen(Some(Fullscreen::Borderless(monitor)));
+    }
+}
+
+/// Opens a recording to watch, printing where its bookmarks are.
+fn open_replay(path: &str) -> Result<(Replay, Simulation), String> {
+    let (replay, sim) = Replay::open(path)?;
+    for bookmark in replay.bookmarks() {
+        let seconds = bookmark.position as f64 / UPDATES_PER_SECOND as f64;
+        println!("{:>7.1}s  {}", seconds, bookmark.label);
+    }
+    Ok((replay, sim))
+}
+
+/// Cuts clips of the highlights of a recording into `dir`, printing the files written.
+fn save_clips(replay: &mut Replay, dir: &str, options: &Options) -> Result<(), String> {
+    let paths = highlights::export(replay, dir, options.clip_format)?;
+    if paths.is_empty() {
+        println!("No highlights to clip");
+    }
+    for path in paths {
+        println!("Saved clip {}", path);
+    }
+    Ok(())
+}
+
+/// Cuts clips from the match just recorded, when asked to with `--clips`.
+fn clip_recording(options: &Options) {
+    let (Some(path), Some(dir)) = (&options.record, &options.clips) else {
+        return;
+    };
+    let exported =
+        Replay::open(path).and_then(|(mut replay, _)| save_clips(&mut replay, dir, options));
+    if let Err(message) = exported {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
+}
+
+/// Optional ways a match is connected to the world outside this machine.
+#[derive(Default)]
+struct Connections {
+    lockstep: Option<Lockstep>,
+    spectators: Option<SpectatorServer>,
+    chat: Option<ChatClient>,
+    control: Option<ControlServer>,
+}
+
+/// Sets up the match, hosting or joining a LAN game, streaming it to spectators, joining a
+/// chat channel and serving the control API when asked to.
+fn start_match(options: &Options) -> Result<(Simulation, Connections), String> {
+    let spectators = options.spectate.map(SpectatorServer::start).transpose()?;
+    let chat = match &options.irc {
+        Some((server, channel)) => Some(ChatClient::connect(server, channel, &options.nick)?),
+        None => None,
+    };
+    let control = options.control.map(ControlServer::start).transpose()?;
+    let seed = options.seed.unwrap_or_else(rand::random);
+    let (sim, lockstep) = match (&options.join, options.host) {
+        (Some(address), _) => {
+            let (lockstep, sim) = Lockstep::join(address)?;
+            (sim, Some(lockstep))
+        }
+        (None, Some(port)) => {
+            let sim = new_simulation(options, seed)?;
+            let delta_time = 1.0 / UPDATES_PER_SECOND as f64;
+            let lockstep = Lockstep::host(port, options.players, &sim, delta_time)?;
+            (sim, Some(lockstep))
+        }
+        (None, None) => match &options.load {
+            Some(path) => {
+                let mut sim = Simulation::load_snapshot(path)?;
+                set_up(&mut sim, options)?;
+                (sim, None)
+            }
+            None => (new_simulation(options, seed)?, None),
+        },
+    };
+    let connections = Connections {
+        lockstep,
+        spectators,
+        chat,
+        control,
+    };
+    Ok((sim, connections))
+}
+
+fn new_simulation(options: &Options, seed: u64) -> Result<Simulation, String> {
+    let mut sim = Simulation::with_arena(seed, load_arena(options)?);
+    if let Some(path) = &options.grid {
+        sim.set_grid_image(path)?;
+    }
+    set_up(&mut sim, options)?;
+    Ok(sim)
+}
+
+fn load_arena(options: &Options) -> Result<Arena, String> {
+    match &options.arena {
+        Some(path) => Arena::load(path),
+        None => Ok(Arena::default()),
+    }
+}
+
+/// Hands the players over to the strategies, bots, scripts and people given in `options`.
+fn set_up(sim: &mut Simulation, options: &Options) -> Result<(), String> {
+    for (id, name) in &options.aims {
+        sim.set_aim(*id, name)?;
+    }
+    for (id, name) in &options.fire_controls {
+        sim.set_fire_control(*id, name)?;
+    }
+    for (id, command) in &options.bots {
+        sim.set_bot(*id, command)?;
+    }
+    for (id, path) in &options.scripts {
+        sim.set_script(*id, path)?;
+    }
+    for (id, path) in &options.params {
+        sim.set_params(*id, CannonParams::load(path)?);
+    }
+    for id in &options.humans {
+        sim.set_human(*id);
+    }
+    Ok(())
+}
+
+/// Plays the setup with the players rotated and mirrored around the field, and prints how
+/// much each seat is favored.
+fn check_balance(options: &Options) -> Result<(), String> {
+    let study = Study {
+        rounds: options.matches,
+        seed: options.seed.unwrap_or_else(rand::random),
+        delta_time: 1.0 / UPDATES_PER_SECOND as f64,
+        max_ticks: options.match_ticks,
+    };
+    println!(
+        "Balance with seed {}: {} rounds of {} arrangements",
+        study.seed,
+        study.rounds,
+        balance::ARRANGEMENTS.len()
+    );
+    let balance = balance::study(&study, |seed, seats| {
+        new_simulation(&options.arranged(seats), seed)
+    })?;
+    for line in balance.report() {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Searches for strong parameters for `seat` against the rest of the setup, printing the
+/// fitness of every generation and saving the best parameters found.
+fn tune(options: &Options, seat: i8) -> Result<(), String> {
+    let search = Search {
+        seat,
+        defaults: CannonParams::for_arena(&load_arena(options)?),
+        generations: options.generations,
+        population: options.population,
+        matches: options.matches,
+        seed: options.seed.unwrap_or_else(rand::random),
+        delta_time: 1.0 / UPDATES_PER_SECOND as f64,
+        max_ticks: options.match_ticks,
+    };
+    println!(
+        "Tuning player {} with seed {}: {} generations of {}, {} matches each",
+        seat, search.seed, search.generations, search.population, search.matches
+    );
+    let best = tuning::evolve(
+        &search,
+        |seed| new_simulation(options, seed),
+        |generation| println!("{}", generation.report()),
+    )?;
+    best.save(&options.params_out)?;
+    println!(
+        "Best parameters saved to {}, play them with --params {}={}",
+        options.params_out, seat, options.params_out
+    );
+    Ok(())
+}
+
+/// Plays every group of entrants against each other, rating them on the ladder as the
+/// matches finish, and prints the leaderboard.
+fn play_season(options: &Options) -> Result<(), String> {
+    let season = Season {
+        rounds: options.matches,
+        seed: options.seed.unwrap_or_else(rand::random),
+        delta_time: 1.0 / UPDATES_PER_SECOND as f64,
+        max_ticks: options.match_ticks,
+    };
+    let mut ladder = Ladder::load(&options.ladder)?;
+    let fixtures = ladder::schedule(&season, options.entrants.len());
+    println!(
+        "Season with seed {}: {} entrants, {} matches",
+        season.seed,
+        options.entrants.len(),
+        fixtures.len()
+    );
+    let orders = ladder::play(&season, &fixtures, |fixture| {
+        let mut setup = options.clone();
+        setup.humans.clear();
+        setup.mouse = None;
+        for (seat, entrant) in &fixture.seats {
+            seat_entrant(&mut setup, *seat, &options.entrants[*entrant]);
+        }
+        new_simulation(&setup, fixture.seed)
+    })?;
+    for (number, order) in orders.iter().enumerate() {
+        let names = order
+            .iter()
+            .map(|entrant| options.entrants[*entrant].clone())
+            .collect::<Vec<String>>();
+        println!("match {:>4}: {}", number + 1, names.join(" > "));
+        ladder.record(&names);
+    }
+    ladder.save(&options.ladder)?;
+    for line in ladder.leaderboard() {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Sets up `seat` for a ladder player, named `<aim>` or `<aim>/<fire>` for the built-in
+/// strategies, `script:<file>`, `bot:<command>` or `params:<file>`.
+fn seat_entrant(options: &mut Options, seat: i8, entrant: &str) {
+    for setup in [
+        &mut options.aims,
+        &mut options.fire_controls,
+        &mut options.bots,
+        &mut options.scripts,
+        &mut options.params,
+    ] {
+        setup.retain(|(id, _)| *id != seat);
+    }
+    if let Some(path) = entrant.strip_prefix("script:") {
+        options.scripts.push((seat, path.to_string()));
+    } else if let Some(command) = entrant.strip_prefix("bot:") {
+        options.bots.push((seat, command.to_string()));
+    } else if let Some(path) = entrant.strip_prefix("params:") {
+        options.params.push((seat, path.to_string()));
+    } else {
+        let (aim, fire) = entrant.split_once('/').unwrap_or((entrant, ""));
+        options.aims.push((seat, aim.to_string()));
+        if !fire.is_empty() {
+            options.fire_controls.push((seat, fire.to_string()));
+        }
+    }
+}
+
+/// Runs `count` matches for a training loop, set up like any other match.
+fn serve_environments(options: &Options, count: usize) -> Result<(), String> {
+    let delta_time = 1.0 / UPDATES_PER_SECOND as f64;
+    let envs = (0..count)
+        .map(|_| {
+            let seed = options.seed.unwrap_or_else(rand::random);
+            let sim = new_simulation(options, seed)?;
+            Ok(Environment::new(
+                sim,
+                delta_time,
+                options.env_step,
+                options.env_limit,
+            ))
+        })
+        .collect::<Result<Vec<Environment>, String>>()?;
+    environment::serve(envs)
+}
+
+fn run_tui(
+    mut sim: Simulation,
+    connections: Connections,
+    mut recorder: Option<Recorder>,
+    mut highlights: Option<Highlights>,
+) {
+    let Connections {
+        mut lockstep,
+        mut spectators,
+        mut chat,
+        mut control,
+    } = connections;
+    let mut tui = TuiRenderer::new();
+    tui.start().expect("Unable to write to terminal");
+
+    let frame_time = Duration::from_secs(1) / TUI_FRAMES_PER_SECOND as u32;
+    let delta_time = 1.0 / UPDATES_PER_SECOND as f64;
+    let ticks_per_frame = (UPDATES_PER_SECOND / TUI_FRAMES_PER_SECOND) as usize;
+    let mut is_paused = false;
+    while sim.alive_count() > 1 && lockstep.as_ref().is_none_or(Lockstep::is_connected) {
+        let frame_start = Instant::now();
+        if let Some(control) = &mut control {
+            control.handle(&mut sim, &mut is_paused);
+        }
+        // before the ticks, so that a recording has it in the next tick only, see `App::update`
+        if let Some(chat) = &mut chat {
+            chat.apply(&mut sim);
+        }
+        // every tick, see `App::update`
+        match &mut lockstep {
+            Some(lockstep) => {
+                lockstep.advance(&mut sim, ticks_per_frame, |sim| {
+                    if let Some(highlights) = &mut highlights {
+                        highlights.observe(sim);
+                    }
+                });
+            }
+            None if is_paused => {}
+            None => {
+                for _ in 0..ticks_per_frame {
+                    sim.update(delta_time);
+                    if let Some(highlights) = &mut highlights {
+                        highlights.observe(&sim);
+                    }
+                }
+            }
+        }
+        if let Some(writer) = &mut recorder {
+            if let Err(message) = writer.record(&mut sim, delta_time) {
+                eprintln!("{}, recording stopped", message);
+                recorder = None;
+            }
+        }
+        if let Some(spectators) = &mut spectators {
+            spectators.publish(&sim);
+        }
+        let notes = chat.iter().map(ChatClient::status).collect::<Vec<String>>();
+        tui.render(&sim, &notes)
+            .expect("Unable to write to terminal");
+        if let Some(remaining) = frame_time.checked_sub(frame_start.elapsed()) {
+            std::thread::sleep(remaining);
+        }
+    }
+    tui.render(&sim, &[]).expect("Unable to write to terminal");
+    tui.finish().expect("Unable to write to terminal");
+    for line in sim.fire_report() {
+        println!("{}", line);
+    }
+    if let Some(lockstep) = lockstep {
+        println!(
+            "LAN player {}: tick {} checksum {:016x} {}",
+            lockstep.seat(),
+            sim.tick(),
+            sim.checksum(),
+            lockstep.status().unwrap_or("in sync")
+        );
+    }
 }
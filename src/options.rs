@@ -0,0 +1,464 @@
+use crate::application::highlights::ClipFormat;
+
+/// Command line options, e.g. `--tui --aim 2=nearest --fire 2=burst`.
+#[derive(Clone)]
+pub struct Options {
+    pub tui: bool,
+    pub fullscreen: bool,
+    pub aims: Vec<(i8, String)>,
+    pub fire_controls: Vec<(i8, String)>,
+    pub humans: Vec<i8>,
+    pub bots: Vec<(i8, String)>,
+    pub scripts: Vec<(i8, String)>,
+    pub params: Vec<(i8, String)>,
+    pub mouse: Option<i8>,
+    pub controls: Option<String>,
+    pub seed: Option<u64>,
+    pub host: Option<u16>,
+    pub players: usize,
+    pub join: Option<String>,
+    pub spectate: Option<u16>,
+    pub watch: Option<String>,
+    pub irc: Option<(String, String)>,
+    pub nick: String,
+    pub control: Option<u16>,
+    pub envs: Option<usize>,
+    pub env_step: u64,
+    pub env_limit: u64,
+    pub tune: Option<i8>,
+    pub generations: usize,
+    pub population: usize,
+    pub matches: usize,
+    pub match_ticks: u64,
+    pub params_out: String,
+    pub balance: bool,
+    pub arena: Option<String>,
+    pub ladder: String,
+    pub leaderboard: bool,
+    pub season: bool,
+    pub entrants: Vec<String>,
+    pub snapshot: Option<String>,
+    pub load: Option<String>,
+    pub grid: Option<String>,
+    pub grid_out: Option<String>,
+    pub rewind: Option<f64>,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    pub highlights: bool,
+    pub clips: Option<String>,
+    pub clip_format: ClipFormat,
+}
+
+impl Options {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+        let mut options = Options {
+            tui: false,
+            fullscreen: false,
+            aims: Vec::new(),
+            fire_controls: Vec::new(),
+            humans: Vec::new(),
+            bots: Vec::new(),
+            scripts: Vec::new(),
+            params: Vec::new(),
+            mouse: None,
+            controls: None,
+            seed: None,
+            host: None,
+            players: 2,
+            join: None,
+            spectate: None,
+            watch: None,
+            irc: None,
+            nick: "colorbattle".to_string(),
+            control: None,
+            envs: None,
+            env_step: 4,
+            env_limit: 36_000,
+            tune: None,
+            generations: 10,
+            population: 12,
+            matches: 4,
+            match_ticks: 144_000,
+            params_out: "best_params.json".to_string(),
+            balance: false,
+            arena: None,
+            ladder: "ladder.json".to_string(),
+            leaderboard: false,
+            season: false,
+            entrants: Vec::new(),
+            snapshot: None,
+            load: None,
+            grid: None,
+            grid_out: None,
+            rewind: None,
+            record: None,
+            replay: None,
+            highlights: false,
+            clips: None,
+            clip_format: ClipFormat::Gif,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--tui" => options.tui = true,
+                "--fullscreen" => options.fullscreen = true,
+                "--aim" => {
+                    let value = args.next().ok_or("--aim needs a value like 2=nearest")?;
+                    options.aims.push(parse_player_value(&value)?);
+                }
+                "--fire" => {
+                    let value = args.next().ok_or("--fire needs a value like 2=burst")?;
+                    options.fire_controls.push(parse_player_value(&value)?);
+                }
+                "--human" => {
+                    let value = args.next().ok_or("--human needs a player from 1 to 4")?;
+                    options.humans.push(parse_player(&value)?);
+                }
+                "--bot" => {
+                    let value = args
+                        .next()
+                        .ok_or("--bot needs a value like 2=\"python3 bot.py\"")?;
+                    options.bots.push(parse_player_value(&value)?);
+                }
+                "--script" => {
+                    let value = args
+                        .next()
+                        .ok_or("--script needs a value like 2=tactics.rhai")?;
+                    options.scripts.push(parse_player_value(&value)?);
+                }
+                "--params" => {
+                    let value = args
+                        .next()
+                        .ok_or("--params needs a value like 2=best_params.json")?;
+                    options.params.push(parse_player_value(&value)?);
+                }
+                "--mouse" => {
+                    let value = args.next().ok_or("--mouse needs a player from 1 to 4")?;
+                    options.mouse = Some(parse_player(&value)?);
+                }
+                "--controls" => {
+                    let value = args.next().ok_or("--controls needs a file name")?;
+                    options.controls = Some(value);
+                }
+                "--seed" => {
+                    let value = args.next().ok_or("--seed needs a number")?;
+                    let seed = value
+                        .parse()
+                        .map_err(|_| format!("Seed must be a number, got '{}'", value))?;
+                    options.seed = Some(seed);
+                }
+                "--host" => {
+                    let value = args.next().ok_or("--host needs a port")?;
+                    options.host = Some(parse_port(&value)?);
+                }
+                "--players" => {
+                    let value = args.next().ok_or("--players needs a number from 2 to 4")?;
+                    options.players = match value.parse::<usize>() {
+                        Ok(players) if (2..=4).contains(&players) => players,
+                        _ => return Err(format!("Players must be 2 to 4, got '{}'", value)),
+                    };
+                }
+                "--join" => {
+                    let value = args
+                        .next()
+                        .ok_or("--join needs an address like host:7777")?;
+                    options.join = Some(value);
+                }
+                "--spectate" => {
+                    let value = args.next().ok_or("--spectate needs a port")?;
+                    options.spectate = Some(parse_port(&value)?);
+                }
+                "--watch" => {
+                    let value = args
+                        .next()
+                        .ok_or("--watch needs a URL like ws://host:7778")?;
+                    options.watch = Some(value);
+                }
+                "--irc" => {
+                    let value = args.next().ok_or(
+                        "--irc needs a server and channel like localhost:6667/colorbattle",
+                    )?;
+                    let (server, channel) = value.split_once('/').ok_or_else(|| {
+                        format!("Expected <host>:<port>/<channel>, got '{}'", value)
+                    })?;
+                    options.irc = Some((server.to_string(), channel.to_string()));
+                }
+                "--control" => {
+                    let value = args.next().ok_or("--control needs a port")?;
+                    options.control = Some(parse_port(&value)?);
+                }
+                "--env" => {
+                    let value = args.next().ok_or("--env needs a number of environments")?;
+                    options.envs = match value.parse::<usize>() {
+                        Ok(count) if count > 0 => Some(count),
+                        _ => {
+                            return Err(format!("Environments must be at least 1, got '{}'", value))
+                        }
+                    };
+                }
+                "--env-step" => {
+                    let value = args.next().ok_or("--env-step needs a number of ticks")?;
+                    options.env_step = parse_ticks(&value)?;
+                }
+                "--env-limit" => {
+                    let value = args.next().ok_or("--env-limit needs a number of ticks")?;
+                    options.env_limit = parse_ticks(&value)?;
+                }
+                "--tune" => {
+                    let value = args.next().ok_or("--tune needs a player from 1 to 4")?;
+                    options.tune = Some(parse_player(&value)?);
+                }
+                "--generations" => {
+                    let value = args.next().ok_or("--generations needs a number")?;
+                    options.generations = parse_count(&value)?;
+                }
+                "--population" => {
+                    let value = args.next().ok_or("--population needs a number")?;
+                    options.population = parse_count(&value)?;
+                }
+                "--matches" => {
+                    let value = args.next().ok_or("--matches needs a number")?;
+                    options.matches = parse_count(&value)?;
+                }
+                "--match-ticks" => {
+                    let value = args.next().ok_or("--match-ticks needs a number of ticks")?;
+                    options.match_ticks = parse_ticks(&value)?;
+                }
+                "--balance" => options.balance = true,
+                "--arena" => {
+                    options.arena = Some(args.next().ok_or("--arena needs a file name")?);
+                }
+                "--ladder" => {
+                    options.ladder = args.next().ok_or("--ladder needs a file name")?;
+                }
+                "--leaderboard" => options.leaderboard = true,
+                "--season" => options.season = true,
+                "--entrant" => {
+                    let value = args
+                        .next()
+                        .ok_or("--entrant needs a player like nearest/value or script:aim.rhai")?;
+                    options.entrants.push(value);
+                }
+                "--snapshot" => {
+                    options.snapshot = Some(args.next().ok_or("--snapshot needs a file name")?);
+                }
+                "--load" => {
+                    options.load = Some(args.next().ok_or("--load needs a snapshot file")?);
+                }
+                "--grid" => {
+                    options.grid = Some(args.next().ok_or("--grid needs a PNG file")?);
+                }
+                "--grid-out" => {
+                    options.grid_out = Some(args.next().ok_or("--grid-out needs a file name")?);
+                }
+                "--rewind" => {
+                    let value = args.next().ok_or("--rewind needs a number of seconds")?;
+                    options.rewind = match value.parse::<f64>() {
+                        Ok(seconds) if seconds >= 0.0 => Some(seconds),
+                        _ => return Err(format!("Expected seconds to rewind, got '{}'", value)),
+                    };
+                }
+                "--record" => {
+                    options.record = Some(args.next().ok_or("--record needs a file name")?);
+                }
+                "--replay" => {
+                    options.replay = Some(args.next().ok_or("--replay needs a recording")?);
+                }
+                "--highlights" => options.highlights = true,
+                "--clips" => {
+                    options.clips = Some(args.next().ok_or("--clips needs a directory")?);
+                }
+                "--clip-format" => {
+                    let value = args.next().ok_or("--clip-format needs gif or png")?;
+                    options.clip_format = ClipFormat::by_name(&value).ok_or_else(|| {
+                        format!("Clip format must be gif or png, got '{}'", value)
+                    })?;
+                }
+                "--params-out" => {
+                    options.params_out = args.next().ok_or("--params-out needs a file name")?;
+                }
+                "--nick" => {
+                    options.nick = args.next().ok_or("--nick needs a name")?;
+                }
+                _ => return Err(format!("Unknown option '{}'", arg)),
+            }
+        }
+        if options.season && options.entrants.len() < 2 {
+            return Err("--season needs at least two --entrant players".to_string());
+        }
+        // tuned numbers aim with a blend of strategies, in place of any other way to play the seat
+        for (seat, _) in &options.params {
+            if let Some(other) = options.aim_option(*seat) {
+                return Err(format!(
+                    "Player {} can't have both --params and {}",
+                    seat, other
+                ));
+            }
+        }
+        if let Some(seat) = options.tune {
+            if let Some(other) = options.aim_option(seat) {
+                return Err(format!(
+                    "Player {} is tuned, so it can't have {}",
+                    seat, other
+                ));
+            }
+            if options.params.iter().any(|(id, _)| *id == seat) {
+                return Err(format!(
+                    "Player {} is tuned, so it can't have --params",
+                    seat
+                ));
+            }
+            // bots answer in their own time, so the same seed would no longer give the same result
+            if !options.bots.is_empty() {
+                return Err("--tune can't be used with --bot".to_string());
+            }
+        }
+        if options.load.is_some() && options.arena.is_some() {
+            return Err(
+                "A snapshot keeps its own arena, so --arena can't be used with --load".to_string(),
+            );
+        }
+        if options.load.is_some() && options.grid.is_some() {
+            return Err(
+                "A snapshot keeps its own grid, so --grid can't be used with --load".to_string(),
+            );
+        }
+        if options.replay.is_some() {
+            if options.tui {
+                return Err(
+                    "--replay plays in the window, so it can't be used with --tui".to_string(),
+                );
+            }
+            if options.record.is_some() {
+                return Err("--replay can't be used with --record".to_string());
+            }
+            if options.load.is_some() {
+                return Err("--replay can't be used with --load".to_string());
+            }
+            // nothing but the recording may change the match on show
+            if options.control.is_some() {
+                return Err("--replay can't be used with --control".to_string());
+            }
+        }
+        // clips are cut from a recording, made while playing or watched again
+        if options.clips.is_some() && options.record.is_none() && options.replay.is_none() {
+            return Err("--clips needs a match to --record or --replay".to_string());
+        }
+        if options.host.is_some() && options.join.is_some() {
+            return Err("Use either --host or --join, not both".to_string());
+        }
+        if options.host.is_some() || options.join.is_some() {
+            // these change the match, but would only reach one copy of the simulation
+            if options.irc.is_some() {
+                return Err("--irc can't be used in a LAN game".to_string());
+            }
+            if options.control.is_some() {
+                return Err("--control can't be used in a LAN game".to_string());
+            }
+            // bots answer in their own time, which no two machines would agree on
+            if !options.bots.is_empty() {
+                return Err("--bot can't be used in a LAN game".to_string());
+            }
+            // scripts are picked up again whenever they change, on each machine in its own time
+            if !options.scripts.is_empty() {
+                return Err("--script can't be used in a LAN game".to_string());
+            }
+            if !options.params.is_empty() {
+                return Err("--params can't be used in a LAN game".to_string());
+            }
+            if options.arena.is_some() {
+                return Err("--arena can't be used in a LAN game".to_string());
+            }
+            if options.grid.is_some() {
+                return Err("--grid can't be used in a LAN game".to_string());
+            }
+            // the others would have to be sent the whole snapshot
+            if options.load.is_some() {
+                return Err("--load can't be used in a LAN game".to_string());
+            }
+            if options.balance {
+                return Err("--balance can't be used in a LAN game".to_string());
+            }
+            if options.season {
+                return Err("--season can't be used in a LAN game".to_string());
+            }
+            if options.tune.is_some() {
+                return Err("--tune can't be used in a LAN game".to_string());
+            }
+            if options.envs.is_some() {
+                return Err("--env can't be used in a LAN game".to_string());
+            }
+            if options.replay.is_some() {
+                return Err("--replay can't be used in a LAN game".to_string());
+            }
+        }
+        Ok(options)
+    }
+
+    /// The option that picks how player `seat` aims, if any.
+    fn aim_option(&self, seat: i8) -> Option<&'static str> {
+        let has = |setup: &[(i8, String)]| setup.iter().any(|(id, _)| *id == seat);
+        if has(&self.aims) {
+            Some("--aim")
+        } else if has(&self.bots) {
+            Some("--bot")
+        } else if has(&self.scripts) {
+            Some("--script")
+        } else {
+            None
+        }
+    }
+
+    /// The same setup with player `i` moved to seat `seats[i - 1]`.
+    pub fn arranged(&self, seats: [i8; 4]) -> Options {
+        let seat = |id: i8| seats[(id - 1) as usize];
+        let mut options = self.clone();
+        for (id, _) in options
+            .aims
+            .iter_mut()
+            .chain(options.fire_controls.iter_mut())
+            .chain(options.bots.iter_mut())
+            .chain(options.scripts.iter_mut())
+            .chain(options.params.iter_mut())
+        {
+            *id = seat(*id);
+        }
+        for id in options.humans.iter_mut().chain(options.mouse.iter_mut()) {
+            *id = seat(*id);
+        }
+        options
+    }
+}
+
+/// Splits `<player>=<value>`, where player is 1 to 4.
+fn parse_player_value(text: &str) -> Result<(i8, String), String> {
+    let (player, value) = text
+        .split_once('=')
+        .ok_or_else(|| format!("Expected <player>=<value>, got '{}'", text))?;
+    Ok((parse_player(player)?, value.to_string()))
+}
+
+fn parse_player(text: &str) -> Result<i8, String> {
+    match text.parse::<i8>() {
+        Ok(id) if (1..=4).contains(&id) => Ok(id),
+        _ => Err(format!("Player must be 1 to 4, got '{}'", text)),
+    }
+}
+
+fn parse_port(text: &str) -> Result<u16, String> {
+    text.parse()
+        .map_err(|_| format!("Port must be a number, got '{}'", text))
+}
+
+fn parse_count(text: &str) -> Result<usize, String> {
+    match text.parse::<usize>() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("Expected a number of at least 1, got '{}'", text)),
+    }
+}
+
+fn parse_ticks(text: &str) -> Result<u64, String> {
+    match text.parse::<u64>() {
+        Ok(ticks) if ticks > 0 => Ok(ticks),
+        _ => Err(format!("Ticks must be at least 1, got '{}'", text)),
+    }
+}